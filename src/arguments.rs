@@ -1,4 +1,5 @@
 use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
 
 /// Program to communicate with large language models and AI API 
 #[derive(Parser, Debug)]
@@ -30,12 +31,45 @@ pub struct Args {
     pub engine: Subcommands,
     /// Format the completion in the terminal
     /// 
-    /// Possible values: markdown, raw
+    /// Possible values: markdown, html, plain, json, raw
     #[arg(long, short, global = true, value_enum, default_value_t = Default::default())]
     pub formatter: FormatterChoice,
+    /// Page the answer once it doesn't fit in the terminal
+    /// 
+    /// The pager is `$PAGER`, or `less` if not set
+    #[arg(long, global = true, conflicts_with = "no_pager")]
+    pub pager: bool,
+    /// Don't page the answer, even if enabled in the configuration file
+    #[arg(long, global = true)]
+    pub no_pager: bool,
     /// Run code block if the language is supported
     #[arg(long, short, global = true, value_enum, default_value_t = Default::default())]
     pub run: RunChoice,
+    /// Run the selected shell code blocks in a single shell process
+    /// 
+    /// The working directory and the exported variables are kept from one code block to the next
+    #[arg(long, global = true)]
+    pub chain: bool,
+    /// Arguments passed to the executed code blocks
+    /// 
+    /// The arguments are split like a shell does (ex: --run-args "file.txt --name 'John Doe'")
+    #[arg(long, global = true)]
+    pub run_args: Option<String>,
+    /// File forwarded to the standard input of the executed code blocks
+    #[arg(long, global = true)]
+    pub run_stdin: Option<String>,
+    /// Working directory of the executed code blocks
+    #[arg(long, global = true)]
+    pub run_cwd: Option<String>,
+    /// Variable of the prompts (ex: --var lang=rust for `$lang`)
+    /// 
+    /// The built-in variables are `$os`, `$shell`, `$cwd`, `$date` and `$user`,
+    /// and `$env_NAME` is the environment variable NAME if listed in `variables.env` of the configuration
+    #[arg(long = "var", global = true, value_name = "NAME=VALUE", value_parser = crate::config::variables::parse_definition)]
+    pub vars: Vec<(String, String)>,
+    /// Variable of the prompts read from a file (ex: --var-file code=main.rs for `$code`)
+    #[arg(long = "var-file", global = true, value_name = "NAME=PATH", value_parser = crate::config::variables::parse_definition)]
+    pub var_files: Vec<(String, String)>,
     /// User text prompt
     /// 
     /// If the text is empty, it will be read from stdin
@@ -52,6 +86,15 @@ pub enum Subcommands {
     FromContent(FromContentArgs),
     /// Display the content of a file
     Local(LocalArgs),
+    /// Browse the history of aio
+    #[command(subcommand)]
+    History(HistoryCommands),
+    /// Manage the prompts of the configuration and of the prompts directory
+    #[command(subcommand)]
+    Prompts(PromptsCommands),
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommands),
 }
 
 /// OpenAI API arguments
@@ -99,12 +142,100 @@ pub enum FormatterChoice {
     /// Markdown display
     #[default]
     Markdown,
+    /// Standalone html page
+    Html,
+    /// Text without the markdown syntax nor escape codes
+    Plain,
+    /// Newline-delimited json events
+    Json,
     /// Raw display
     Raw,
 }
 
-#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+/// History subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum HistoryCommands {
+    /// Browse the log of the executed code blocks
+    Runs(HistoryRunsArgs),
+}
+
+/// History runs arguments
+#[derive(ClapArgs, Debug, Clone)]
+pub struct HistoryRunsArgs {
+    /// Number of entries to display
+    /// 
+    /// Only the last entries of the log are displayed
+    #[arg(long, short = 'n', default_value_t = 20)]
+    pub last: usize,
+    /// Display the full entry of the given run id, including the code
+    #[arg(long, short)]
+    pub show: Option<usize>,
+    /// Run again the code of the given run id
+    #[arg(long)]
+    pub rerun: Option<usize>,
+    /// Run again without asking for confirmation
+    #[arg(long, short, requires = "rerun")]
+    pub yes: bool,
+}
+
+/// Prompts subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum PromptsCommands {
+    /// List the prompts with the file defining them
+    List,
+    /// Display a prompt, resolved with the prompt it extends
+    Show(PromptsShowArgs),
+    /// Create a prompt file in the prompts directory
+    New(PromptsNewArgs),
+}
+
+/// Prompts show arguments
+#[derive(ClapArgs, Debug, Clone)]
+pub struct PromptsShowArgs {
+    /// Prompt name
+    pub name: String,
+}
+
+/// Prompts new arguments
+#[derive(ClapArgs, Debug, Clone)]
+pub struct PromptsNewArgs {
+    /// Prompt name, used as file name
+    pub name: String,
+    /// Write the prompt in yaml instead of markdown
+    #[arg(long)]
+    pub yaml: bool,
+}
+
+/// Config subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommands {
+    /// Display the effective configuration, merged from all the layers
+    Show(ConfigShowArgs),
+    /// Report the unknown keys, the invalid values and the undefined variables of the configuration
+    Check,
+    /// Print the JSON Schema of the configuration file, for the editors with a YAML language server
+    Schema(ConfigSchemaArgs),
+}
+
+/// Config show arguments
+#[derive(ClapArgs, Debug, Clone)]
+pub struct ConfigShowArgs {
+    /// Display each value with the file or the environment variable it comes from
+    #[arg(long)]
+    pub origin: bool,
+}
+
+/// Config schema arguments
+#[derive(ClapArgs, Debug, Clone)]
+pub struct ConfigSchemaArgs {
+    /// Print the schema of the credentials file instead
+    #[arg(long)]
+    pub creds: bool,
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
 #[value(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum RunChoice {
     /// Doesn't run anything
     #[default]
@@ -113,4 +244,4 @@ pub enum RunChoice {
     Ask,
     /// Run code without asking
     Force
-}
\ No newline at end of file
+}
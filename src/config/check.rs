@@ -0,0 +1,401 @@
+//! Diagnostics of the configuration, with the file, the line and the column of the values
+//!
+//! The configuration structs deny the unknown fields. Unless `strict` is set, the unknown
+//! fields are reported as warnings and removed before the configuration is read again,
+//! so that the configuration files written for the previous versions still work.
+
+use std::path::Path;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_yaml::Value;
+use super::Config;
+use super::layers::{self, Layers, Origin};
+use super::variables::{Variables, VariableError};
+
+/// Errors read again after removing the erroneous value, for `aio config check`
+const MAX_DIAGNOSTICS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Problem of the configuration, at the location of the value if known
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// File, line and column, or environment variable
+    pub location: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}", location, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, location: None, message: message.into() }
+    }
+    fn warning(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, location: None, message: message.into() }
+    }
+    /// Located at the value of the path in the layer it comes from
+    fn at(mut self, layers: &Layers, path: &str) -> Self {
+        self.location = locate(layers, path);
+        self
+    }
+}
+
+/// Read the configuration from the merged layers
+///
+/// Returns the configuration and the warnings of the unknown fields, or the first error.
+pub fn deserialize(layers: &Layers) -> Result<(Config, Vec<Diagnostic>), Diagnostic> {
+    let strict = layers.value.get("strict").and_then(Value::as_bool).unwrap_or_default();
+    let (config, diagnostics) = deserialize_lenient(layers, strict, false);
+    match config {
+        Some(config) => Ok((config, diagnostics)),
+        None => Err(diagnostics.into_iter().rev().find(|d| d.severity == Severity::Error).unwrap_or_else(|| Diagnostic::error("invalid configuration"))),
+    }
+}
+
+/// Read the configuration, the erroneous values are removed until it is valid
+///
+/// The unknown fields are warnings unless `strict`, the other errors stop unless `all_errors`.
+fn deserialize_lenient(layers: &Layers, strict: bool, all_errors: bool) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut value = layers.value.clone();
+    let mut diagnostics = Vec::new();
+    let mut removed_paths = Vec::<String>::new();
+    while diagnostics.len() < MAX_DIAGNOSTICS {
+        let error = match serde_path_to_error::deserialize::<_, Config>(&value) {
+            Ok(config) => return (Some(config), diagnostics),
+            Err(error) => error,
+        };
+        let message = with_suggestion(&error.inner().to_string());
+        let unknown_field = message.starts_with("unknown field");
+        let path = named_path(&value, error.path());
+        // a required field removed because of a previous error
+        let consequence = message.strip_prefix("missing field `").and_then(|field| field.strip_suffix('`'))
+            .is_some_and(|field| removed_paths.contains(&layers::key_path(&path, &Value::String(field.to_string()))));
+        if !consequence {
+            let message = if path.is_empty() { message } else { format!("`{}`: {}", path, message) };
+            let diagnostic = if unknown_field && !strict { Diagnostic::warning(message) } else { Diagnostic::error(message) };
+            diagnostics.push(diagnostic.at(layers, &path));
+        }
+        let removable = (unknown_field && !strict) || all_errors;
+        if !removable || !remove(&mut value, error.path()) {
+            return (None, diagnostics);
+        }
+        removed_paths.push(path);
+    }
+    (None, diagnostics)
+}
+
+/// Message of the unknown field or variant with the closest expected name, if any
+fn with_suggestion(message: &str) -> String {
+    static UNKNOWN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^unknown (field|variant) `([^`]*)`, expected (.*)$").expect("Failed to compile regex"));
+    static NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"`([^`]*)`").expect("Failed to compile regex"));
+    let Some(caps) = UNKNOWN.captures(message) else { return message.to_string() };
+    let normalize = |name: &str| name.chars().filter(|c| !matches!(c, '-' | '_')).collect::<String>().to_lowercase();
+    let unknown = normalize(&caps[2]);
+    let suggestion = NAME.captures_iter(&caps[3])
+        .map(|name| (strsim::jaro_winkler(&unknown, &normalize(&name[1])), name[1].to_string()))
+        .filter(|(score, _)| *score >= 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    match suggestion {
+        Some((_, name)) => format!("unknown {} `{}`, did you mean `{}`?", &caps[1], &caps[2], name),
+        None => message.to_string(),
+    }
+}
+
+/// Path of the value, with the items of the named sequences by name
+fn named_path(value: &Value, path: &serde_path_to_error::Path) -> String {
+    let mut current = Some(value);
+    let mut named = String::new();
+    for segment in path {
+        let node = current.map(untag);
+        match segment {
+            serde_path_to_error::Segment::Seq { index } => {
+                let item = node.and_then(|node| node.get(index));
+                named = match item {
+                    Some(item) => layers::item_path(&named, *index, item),
+                    None => format!("{}[{}]", named, index),
+                };
+                current = item;
+            }
+            serde_path_to_error::Segment::Map { key } | serde_path_to_error::Segment::Enum { variant: key } => {
+                named = layers::key_path(&named, &Value::String(key.clone()));
+                current = node.and_then(|node| node.get(key.as_str()));
+            }
+            serde_path_to_error::Segment::Unknown => current = None,
+        }
+    }
+    named
+}
+
+fn untag(value: &Value) -> &Value {
+    match value {
+        Value::Tagged(tagged) => &tagged.value,
+        value => value,
+    }
+}
+
+/// Remove the value at the path, returns false if it is not found
+fn remove(value: &mut Value, path: &serde_path_to_error::Path) -> bool {
+    let segments = path.iter().collect::<Vec<_>>();
+    let Some((last, parents)) = segments.split_last() else { return false };
+    let mut current = value;
+    for segment in parents {
+        if let Value::Tagged(tagged) = current {
+            current = &mut tagged.value;
+        }
+        let next = match segment {
+            serde_path_to_error::Segment::Seq { index } => current.get_mut(index),
+            serde_path_to_error::Segment::Map { key } | serde_path_to_error::Segment::Enum { variant: key } => current.get_mut(key.as_str()),
+            serde_path_to_error::Segment::Unknown => None,
+        };
+        let Some(next) = next else { return false };
+        current = next;
+    }
+    if let Value::Tagged(tagged) = current {
+        current = &mut tagged.value;
+    }
+    match (current, last) {
+        (Value::Sequence(items), serde_path_to_error::Segment::Seq { index }) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        (Value::Mapping(mapping), serde_path_to_error::Segment::Map { key }) => mapping.remove(key.as_str()).is_some(),
+        _ => false,
+    }
+}
+
+/// File, line and column of the value at the path, or the environment variable
+fn locate(layers: &Layers, path: &str) -> Option<String> {
+    match layers::origin(&layers.origins, path)? {
+        Origin::Env(name) => Some(format!("environment variable {}", name)),
+        Origin::File(file) => {
+            let content = std::fs::read_to_string(file).ok()?;
+            let value: Value = serde_yaml::from_str(&content).ok()?;
+            let position = indexed_path(&value, path)
+                .and_then(|keys| Locate(&keys).deserialize(serde_yaml::Deserializer::from_str(&content)).err())
+                .and_then(|error| error.location());
+            match position {
+                Some(position) => Some(format!("{}:{}:{}", file.display(), position.line(), position.column())),
+                None => Some(file.display().to_string()),
+            }
+        }
+    }
+}
+
+/// Keys of the mappings and indexes of the sequences of the path in the value,
+/// the deepest value found if the path is not complete
+fn indexed_path(value: &Value, path: &str) -> Option<Vec<String>> {
+    static SEGMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[([^\]]*)\]|\.?([^.\[]+)").expect("Failed to compile regex"));
+    let mut keys = Vec::new();
+    let mut current = value;
+    for caps in SEGMENT.captures_iter(path) {
+        current = untag(current);
+        let next = match (caps.get(1), caps.get(2), current) {
+            (Some(name), _, Value::Sequence(items)) => items.iter()
+                .position(|item| item.get("name").and_then(Value::as_str) == Some(name.as_str()))
+                .or_else(|| name.as_str().parse().ok().filter(|index| *index < items.len()))
+                .map(|index| (index.to_string(), &items[index])),
+            (_, Some(key), Value::Mapping(mapping)) => mapping.get(key.as_str()).map(|value| (key.as_str().to_string(), value)),
+            _ => None,
+        };
+        let Some((key, value)) = next else { break };
+        keys.push(key);
+        current = value;
+    }
+    Some(keys)
+}
+
+/// Finds the value at the keys, by failing there so that the error has its location
+struct Locate<'a>(&'a [String]);
+
+impl<'de> DeserializeSeed<'de> for Locate<'_> {
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Locate<'_> {
+    type Value = ();
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any value")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Some((key, rest)) = self.0.split_first() else { return Err(serde::de::Error::custom("found")) };
+        loop {
+            // the location of a value of a mapping is the one of its key
+            let found = match rest.is_empty() {
+                true => map.next_key_seed(FindKey(key))?,
+                false => map.next_key::<Value>()?.map(|map_key| map_key.as_str() == Some(key)),
+            };
+            match found {
+                None => return Ok(()),
+                Some(true) => return map.next_value_seed(Locate(rest)),
+                Some(false) => map.next_value::<IgnoredAny>().map(|_| ())?,
+            }
+        }
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Some((index, rest)) = self.0.split_first() else { return Err(serde::de::Error::custom("found")) };
+        let index = index.parse::<usize>().unwrap_or(usize::MAX);
+        for _ in 0..index {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(());
+            }
+        }
+        seq.next_element_seed(Locate(rest)).map(|_| ())
+    }
+    fn visit_enum<A: serde::de::EnumAccess<'de>>(self, data: A) -> Result<(), A::Error> {
+        let (_, variant) = data.variant::<Value>()?;
+        serde::de::VariantAccess::newtype_variant_seed(variant, self)
+    }
+    fn visit_bool<E: serde::de::Error>(self, _: bool) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+    fn visit_i64<E: serde::de::Error>(self, _: i64) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+    fn visit_u64<E: serde::de::Error>(self, _: u64) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+    fn visit_f64<E: serde::de::Error>(self, _: f64) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+    fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+    fn visit_unit<E: serde::de::Error>(self) -> Result<(), E> {
+        Err(E::custom("found"))
+    }
+}
+
+/// Key of a mapping, failing if it is the key searched by [`Locate`]
+struct FindKey<'a>(&'a str);
+
+impl<'de> DeserializeSeed<'de> for FindKey<'_> {
+    type Value = bool;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<bool, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FindKey<'_> {
+    type Value = bool;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a key")
+    }
+    fn visit_str<E: serde::de::Error>(self, key: &str) -> Result<bool, E> {
+        match key == self.0 {
+            true => Err(E::custom("found")),
+            false => Ok(false),
+        }
+    }
+    fn visit_bool<E>(self, _: bool) -> Result<bool, E> {
+        Ok(false)
+    }
+    fn visit_i64<E>(self, _: i64) -> Result<bool, E> {
+        Ok(false)
+    }
+    fn visit_u64<E>(self, _: u64) -> Result<bool, E> {
+        Ok(false)
+    }
+    fn visit_f64<E>(self, _: f64) -> Result<bool, E> {
+        Ok(false)
+    }
+    fn visit_unit<E>(self) -> Result<bool, E> {
+        Ok(false)
+    }
+}
+
+/// All the problems of the configuration merged from the layers, for `aio config check`
+pub fn check(config_path: &Path, variables: &Variables) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut layers = Layers::default();
+    let layer_error = |path: &Path, error: layers::LayerError| {
+        let location = match &error {
+            layers::LayerError::Yaml { source, .. } => source.location().map(|l| format!("{}:{}:{}", path.display(), l.line(), l.column())),
+            _ => None,
+        };
+        Diagnostic { location, ..Diagnostic::error(error.to_string()) }
+    };
+    for path in Config::layer_paths(config_path) {
+        if let Err(error) = layers.merge_file(&path) {
+            diagnostics.push(layer_error(&path, error));
+        }
+    }
+    if let Some(path) = Config::project_path() {
+        match layers.merge_project_file(&path) {
+            Ok(ignored) => diagnostics.extend(ignored.into_iter().map(|key| Diagnostic {
+                location: Some(path.display().to_string()),
+                ..Diagnostic::warning(format!("`{}` is ignored, a project file can't set it", key))
+            })),
+            Err(error) => diagnostics.push(layer_error(&path, error)),
+        }
+    }
+    if let Err(error) = layers.merge_env(std::env::vars()) {
+        diagnostics.push(Diagnostic::error(error.to_string()));
+    }
+    let strict = layers.value.get("strict").and_then(Value::as_bool).unwrap_or_default();
+    let (config, deserialize_diagnostics) = deserialize_lenient(&layers, strict, true);
+    diagnostics.extend(deserialize_diagnostics);
+    let Some(mut config) = config else { return diagnostics };
+
+    match super::library::load(&super::library::prompts_dir()) {
+        Ok(library) => {
+            for (name, path) in super::library::merge(&mut config.prompts, library) {
+                layers.origins.insert(format!("prompts[{}]", name), Origin::File(path));
+            }
+        }
+        Err(error) => diagnostics.push(Diagnostic::error(error.to_string())),
+    }
+    if let Err(error) = config.prompts.resolve(&config.fragments) {
+        diagnostics.push(Diagnostic::error(error.to_string()).at(&layers, "prompts"));
+        return diagnostics;
+    }
+    let mut variables = variables.clone();
+    super::variables::add_env(&mut variables, &config.variables);
+    for prompt in &config.prompts.0 {
+        if let Err(error) = prompt.formatted_messages(&variables) {
+            let path = format!("prompts[{}].messages[{}]", prompt.name, error.index - 1);
+            let diagnostic = match error.source {
+                super::prompt::FormatError::Variable(VariableError::Missing(_))
+                | super::prompt::FormatError::Template(super::template::TemplateError::Variable { source: VariableError::Missing(_), .. }) => {
+                    Diagnostic::warning(format!("{}, it must be given with `--var`", error))
+                }
+                _ => Diagnostic::error(error.to_string()),
+            };
+            diagnostics.push(diagnostic.at(&layers, &path));
+        }
+    }
+    #[cfg(feature = "local-llm")]
+    for model in &config.local.models {
+        let path = crate::filesystem::resolve_path(&model.path);
+        if !Path::new(path.as_ref()).is_file() {
+            let diagnostic = Diagnostic::error(format!("model `{}`: file `{}` not found", model.name, path));
+            diagnostics.push(diagnostic.at(&layers, &format!("local.models[{}].path", model.name)));
+        }
+    }
+    diagnostics
+}
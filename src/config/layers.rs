@@ -0,0 +1,359 @@
+//! Configuration merged from the layers: the system file, the user file, the nearest project
+//! file, then the `AIO_*` environment variables
+//!
+//! The mappings are merged key by key, and the sequences whose items all have a `name`
+//! (the prompts, the local models) item by item. The other values of a layer replace the ones
+//! of the layers below.
+//!
+//! The project file comes with the repository of the working directory, it is not trusted:
+//! only the keys of [`PROJECT_KEYS`] are merged, the other ones are ignored.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use once_cell::sync::Lazy;
+use schemars::schema::{RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+/// Prefix of the environment variables overriding the configuration
+pub const ENV_PREFIX: &str = "AIO_";
+/// Separator of the keys in the name of the environment variables
+/// (ex: `AIO_MARKDOWN__PAGER` for `markdown.pager`)
+pub const ENV_SEPARATOR: &str = "__";
+/// Names of the project files, searched from the working directory up to the root
+pub const PROJECT_FILE_NAMES: [&str; 2] = [".aio.yml", ".aio.yaml"];
+/// Keys the project file can set, the other ones could run commands or change the files read
+pub const PROJECT_KEYS: [&str; 3] = ["prompts", "fragments", "markdown"];
+/// Keys of [`PROJECT_KEYS`] the project file can't set
+pub const PROJECT_DENIED_KEYS: [&str; 1] = ["markdown.pager_command"];
+
+/// Layer of each value, by path (ex: `prompts[ask].parameters.temperature`)
+pub type Origins = HashMap<String, Origin>;
+
+/// Layer a value comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    File(PathBuf),
+    Env(String),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(name) => write!(f, "environment variable {}", name),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LayerError {
+    #[error("failed to read `{}`: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("invalid configuration `{}`: {source}", path.display())]
+    Yaml { path: PathBuf, source: serde_yaml::Error },
+    #[error("invalid environment variable `{name}`: {message}")]
+    Env { name: String, message: String },
+}
+
+/// Configuration file shared by the users of the system
+pub fn system_path() -> PathBuf {
+    #[cfg(windows)]
+    let dir = PathBuf::from(std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".into()));
+    #[cfg(not(windows))]
+    let dir = PathBuf::from("/etc");
+    dir.join("aio").join("config.yml")
+}
+
+/// Nearest project file, in the directory or in one of its parents
+pub fn project_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| PROJECT_FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Values of the layers merged so far, and the layer of each value
+#[derive(Debug)]
+pub struct Layers {
+    pub value: Value,
+    pub origins: Origins,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            value: Value::Mapping(Mapping::new()),
+            origins: Origins::new(),
+        }
+    }
+}
+
+impl Layers {
+    /// Merge the yaml file, an empty file is an empty layer
+    pub fn merge_file(&mut self, path: &Path) -> Result<(), LayerError> {
+        let content = std::fs::read_to_string(path).map_err(|source| LayerError::Io { path: path.to_path_buf(), source })?;
+        let value: Value = serde_yaml::from_str(&content).map_err(|source| LayerError::Yaml { path: path.to_path_buf(), source })?;
+        if !value.is_null() {
+            self.merge(value, Origin::File(path.to_path_buf()));
+        }
+        Ok(())
+    }
+    /// Merge the project file, without the keys it is not allowed to set
+    ///
+    /// Returns the paths of the ignored keys.
+    pub fn merge_project_file(&mut self, path: &Path) -> Result<Vec<String>, LayerError> {
+        let content = std::fs::read_to_string(path).map_err(|source| LayerError::Io { path: path.to_path_buf(), source })?;
+        let mut value: Value = serde_yaml::from_str(&content).map_err(|source| LayerError::Yaml { path: path.to_path_buf(), source })?;
+        let ignored = retain_project_keys(&mut value);
+        if !value.is_null() {
+            self.merge(value, Origin::File(path.to_path_buf()));
+        }
+        Ok(ignored)
+    }
+    /// Merge the value of the layer over the current values
+    pub fn merge(&mut self, value: Value, origin: Origin) {
+        let mut origins = Vec::new();
+        merge_value(&mut self.value, value, String::new(), &mut origins);
+        self.origins.extend(origins.into_iter().map(|path| (path, origin.clone())));
+    }
+    /// Merge the `AIO_*` variables, the value is parsed as yaml (ex: `AIO_MARKDOWN__PAGER=true`)
+    ///
+    /// The keys are compared to the existing ones and to the fields of the configuration regardless
+    /// of case, `-` and `_` (`MAX_TOKENS` is `maxTokens`), the items of the named sequences are selected
+    /// by name or by index. The variables whose first key is not a field of the configuration are ignored.
+    pub fn merge_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<(), LayerError> {
+        let mut vars = vars.into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect::<Vec<_>>();
+        vars.sort();
+        for (name, raw_value) in vars {
+            let env_error = |message: String| LayerError::Env { name: name.clone(), message };
+            let keys = name[ENV_PREFIX.len()..].split(ENV_SEPARATOR).collect::<Vec<_>>();
+            if keys.iter().any(|key| key.is_empty()) {
+                return Err(env_error("empty key".into()));
+            }
+            let value = serde_yaml::from_str::<Value>(&raw_value).unwrap_or_else(|_| Value::String(raw_value.clone()));
+            let Some((target, path)) = select(&mut self.value, &keys).map_err(env_error)? else {
+                log::debug!("Environment variable `{}` is ignored, `{}` is not a field of the configuration", name, keys[0]);
+                continue;
+            };
+            let mut origins = Vec::new();
+            merge_value(target, value, path, &mut origins);
+            self.origins.extend(origins.into_iter().map(|path| (path, Origin::Env(name.clone()))));
+        }
+        Ok(())
+    }
+}
+
+/// Remove the keys the project file is not allowed to set, and return their paths
+fn retain_project_keys(value: &mut Value) -> Vec<String> {
+    let Value::Mapping(mapping) = value else { return Vec::new() };
+    let mut ignored = Vec::new();
+    mapping.retain(|key, _| {
+        let path = key_path("", key);
+        let allowed = PROJECT_KEYS.contains(&path.as_str());
+        if !allowed {
+            ignored.push(path);
+        }
+        allowed
+    });
+    for denied in PROJECT_DENIED_KEYS {
+        let (parent, key) = denied.split_once('.').expect("denied keys are nested");
+        if let Some(Value::Mapping(parent)) = mapping.get_mut(parent) {
+            if parent.remove(key).is_some() {
+                ignored.push(denied.to_string());
+            }
+        }
+    }
+    ignored
+}
+
+/// Layer of the value at the path, or of the nearest parent merged as a whole
+pub fn origin<'a>(origins: &'a Origins, path: &str) -> Option<&'a Origin> {
+    let mut path = path;
+    loop {
+        if let Some(origin) = origins.get(path) {
+            return Some(origin);
+        }
+        path = &path[..path.rfind(['.', '['])?];
+    }
+}
+
+/// Values which are not mappings nor sequences, by path
+pub fn flatten(value: &Value) -> Vec<(String, &Value)> {
+    let mut leaves = Vec::new();
+    flatten_into(value, String::new(), &mut leaves);
+    leaves
+}
+
+fn flatten_into<'a>(value: &'a Value, path: String, leaves: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            for (key, value) in mapping {
+                flatten_into(value, key_path(&path, key), leaves);
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            for (index, item) in items.iter().enumerate() {
+                flatten_into(item, item_path(&path, index, item), leaves);
+            }
+        }
+        Value::Tagged(tagged) => flatten_into(&tagged.value, path, leaves),
+        value => leaves.push((path, value)),
+    }
+}
+
+pub(crate) fn key_path(path: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        key => serde_yaml::to_string(key).unwrap_or_default().trim_end().to_string(),
+    };
+    if path.is_empty() { key } else { format!("{}.{}", path, key) }
+}
+
+/// Path of the item of a sequence, by name if it has one
+pub(crate) fn item_path(path: &str, index: usize, item: &Value) -> String {
+    match item_name(item) {
+        Some(name) => format!("{}[{}]", path, name),
+        None => format!("{}[{}]", path, index),
+    }
+}
+
+fn item_name(item: &Value) -> Option<&str> {
+    item.get("name")?.as_str()
+}
+
+fn is_named_sequence(items: &[Value]) -> bool {
+    items.iter().all(|item| item_name(item).is_some())
+}
+
+/// Merge the value at the path, the paths of the merged values are added to `origins`
+fn merge_value(target: &mut Value, value: Value, path: String, origins: &mut Vec<String>) {
+    match (target, value) {
+        (Value::Mapping(target), Value::Mapping(mapping)) => {
+            for (key, value) in mapping {
+                let key_path = key_path(&path, &key);
+                match target.get_mut(&key) {
+                    Some(target) => merge_value(target, value, key_path, origins),
+                    None => {
+                        record(&value, key_path, origins);
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(target), Value::Sequence(items)) if is_named_sequence(target) && is_named_sequence(&items) => {
+            for item in items {
+                let item_path = item_path(&path, 0, &item);
+                match target.iter_mut().find(|target| item_name(target) == item_name(&item)) {
+                    Some(target) => merge_value(target, item, item_path, origins),
+                    None => {
+                        record(&item, item_path, origins);
+                        target.push(item);
+                    }
+                }
+            }
+        }
+        (target, value) => {
+            record(&value, path, origins);
+            *target = value;
+        }
+    }
+}
+
+/// Record the path of the value and of the values it contains
+fn record(value: &Value, path: String, origins: &mut Vec<String>) {
+    origins.extend(flatten(value).into_iter().map(|(leaf, _)| format!("{}{}", path, leaf_suffix(&leaf))));
+    origins.push(path);
+}
+
+/// Path of the leaf relative to its parent, which starts with `.` for a key
+fn leaf_suffix(leaf: &str) -> String {
+    if leaf.is_empty() || leaf.starts_with('[') { leaf.to_string() } else { format!(".{}", leaf) }
+}
+
+/// Value at the keys of an environment variable, created if missing, and its path
+///
+/// None if the first key is not a field of the configuration.
+fn select<'a>(value: &'a mut Value, keys: &[&str]) -> Result<Option<(&'a mut Value, String)>, String> {
+    let normalize = |key: &str| key.chars().filter(|c| !matches!(c, '-' | '_')).collect::<String>().to_lowercase();
+    let mut current = value;
+    let mut schema = Some(&CONFIG_SCHEMA.schema);
+    let mut path = String::new();
+    for (index, key) in keys.iter().enumerate() {
+        if current.is_null() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        let object = schema.map(resolve);
+        current = match current {
+            Value::Mapping(mapping) => {
+                let field = object.and_then(|object| object.object.as_ref())
+                    .and_then(|object| object.properties.iter().find(|(name, _)| normalize(name) == normalize(key)));
+                if field.is_none() && index == 0 {
+                    return Ok(None);
+                }
+                schema = match field {
+                    Some((_, field)) => schema_object(field),
+                    None => object.and_then(|object| object.object.as_ref())
+                        .and_then(|object| object.additional_properties.as_deref())
+                        .and_then(schema_object),
+                };
+                let existing = mapping.keys()
+                    .find(|k| k.as_str().is_some_and(|k| normalize(k) == normalize(key)))
+                    .cloned();
+                let mapping_key = existing
+                    .or_else(|| field.map(|(name, _)| Value::String(name.clone())))
+                    .unwrap_or_else(|| Value::String(key.to_lowercase()));
+                path = key_path(&path, &mapping_key);
+                mapping.entry(mapping_key).or_insert(Value::Null)
+            }
+            Value::Sequence(items) => {
+                let index = items.iter()
+                    .position(|item| item_name(item).is_some_and(|name| normalize(name) == normalize(key)))
+                    .or_else(|| key.parse::<usize>().ok().filter(|index| *index < items.len()))
+                    .ok_or_else(|| format!("no item `{}` in `{}`", key, path))?;
+                schema = object.and_then(|object| object.array.as_ref())
+                    .and_then(|array| match &array.items {
+                        Some(SingleOrVec::Single(items)) => schema_object(items),
+                        _ => None,
+                    });
+                path = item_path(&path, index, &items[index]);
+                &mut items[index]
+            }
+            _ => return Err(format!("`{}` is not a mapping", path)),
+        };
+    }
+    Ok(Some((current, path)))
+}
+
+/// Schema of the configuration, giving the names of the fields of the environment variables
+static CONFIG_SCHEMA: Lazy<RootSchema> = Lazy::new(super::schema::config_schema);
+
+fn schema_object(schema: &Schema) -> Option<&SchemaObject> {
+    match schema {
+        Schema::Object(object) => Some(object),
+        Schema::Bool(_) => None,
+    }
+}
+
+/// Schema of the definition of the reference, or of the first subschema which is not `null`
+fn resolve(schema: &SchemaObject) -> &SchemaObject {
+    if let Some(definition) = schema.reference.as_deref()
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+        .and_then(|name| CONFIG_SCHEMA.definitions.get(name))
+        .and_then(schema_object)
+    {
+        return resolve(definition);
+    }
+    let subschema = schema.subschemas.as_ref()
+        .and_then(|subschemas| subschemas.all_of.as_ref().or(subschemas.any_of.as_ref()))
+        .and_then(|subschemas| subschemas.iter().filter_map(schema_object).find(|subschema| !is_null(subschema)));
+    match subschema {
+        Some(subschema) => resolve(subschema),
+        None => schema,
+    }
+}
+
+fn is_null(schema: &SchemaObject) -> bool {
+    matches!(&schema.instance_type, Some(SingleOrVec::Single(instance_type)) if **instance_type == schemars::schema::InstanceType::Null)
+}
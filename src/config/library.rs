@@ -0,0 +1,224 @@
+//! Prompts of the library, one file per prompt in `<config_dir>/prompts`
+//!
+//! A `.yml` file contains a prompt like the ones of the configuration file. A `.md` file
+//! starts with an optional front-matter holding the other fields of the prompt, followed by
+//! the messages, each under a `## system`, `## user` or `## assistant` heading.
+//! The name of the prompt is the name of the file if not given.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use super::Config;
+use super::prompt::{Message, Prompt, Prompts, Role};
+use crate::args;
+
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+#[derive(Error, Debug)]
+pub enum LibraryError {
+    #[error("failed to read `{}`: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("invalid prompt `{}`: {source}", path.display())]
+    Yaml { path: PathBuf, source: serde_yaml::Error },
+    #[error("invalid prompt `{}`, line {line}: {message}", path.display())]
+    Markdown { path: PathBuf, line: usize, message: String },
+    #[error("prompt `{0}` not found")]
+    NotFound(String),
+    #[error("prompt `{0}` already exists")]
+    AlreadyExists(String),
+    #[error("failed to serialize the prompt: {0}")]
+    Serialize(#[from] serde_yaml::Error),
+}
+
+/// Directory of the prompt files
+pub fn prompts_dir() -> PathBuf {
+    Path::new(crate::filesystem::config_dir()).join("prompts")
+}
+
+/// Prompts of the files of the directory, sorted by file name
+pub fn load(dir: &Path) -> Result<Vec<(PathBuf, Prompt)>, LibraryError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let io_error = |source| LibraryError::Io { path: dir.to_path_buf(), source };
+    let mut paths = std::fs::read_dir(dir).map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>().map_err(io_error)?;
+    paths.retain(|path| path.is_file() && matches!(extension(path), Some("yml" | "yaml" | "md")));
+    paths.sort();
+    paths.into_iter()
+        .map(|path| {
+            let prompt = load_file(&path)?;
+            Ok((path, prompt))
+        })
+        .collect()
+}
+
+/// Prompt of the file, written in yaml or in markdown depending on its extension
+pub fn load_file(path: &Path) -> Result<Prompt, LibraryError> {
+    let content = std::fs::read_to_string(path).map_err(|source| LibraryError::Io { path: path.to_path_buf(), source })?;
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    match extension(path) {
+        Some("md") => parse_markdown(&content, &name).map_err(|(line, message)| LibraryError::Markdown { path: path.to_path_buf(), line, message }),
+        _ => parse_yaml(&content, &name).map_err(|source| LibraryError::Yaml { path: path.to_path_buf(), source }),
+    }
+}
+
+/// Add the prompts of the library, the prompts already defined win over the ones with the same name
+///
+/// Returns the file of each added prompt, by name.
+pub fn merge(prompts: &mut Prompts, library: Vec<(PathBuf, Prompt)>) -> HashMap<String, PathBuf> {
+    let mut paths = HashMap::<String, PathBuf>::new();
+    for (path, prompt) in library {
+        if let Some(first_path) = paths.get(&prompt.name) {
+            log::warn!("Prompt `{}` of `{}` is ignored, it is already defined by `{}`", prompt.name, path.display(), first_path.display());
+        } else if prompts.0.iter().any(|p| p.name == prompt.name) {
+            log::warn!("Prompt `{}` of `{}` is ignored, it is already defined in the configuration file", prompt.name, path.display());
+        } else {
+            paths.insert(prompt.name.clone(), path);
+            prompts.0.push(prompt);
+        }
+    }
+    paths
+}
+
+pub fn command(command: &args::PromptsCommands, config: &Config) -> Result<(), LibraryError> {
+    match command {
+        args::PromptsCommands::List => list(config),
+        args::PromptsCommands::Show(args) => show(args, config),
+        args::PromptsCommands::New(args) => new(args, config),
+    }
+}
+
+fn list(config: &Config) -> Result<(), LibraryError> {
+    let width = config.prompts.0.iter().map(|prompt| prompt.name.len()).max().unwrap_or_default();
+    for prompt in &config.prompts.0 {
+        println!("{:<width$}  {}", prompt.name, config.origin(&format!("prompts[{}]", prompt.name)));
+    }
+    Ok(())
+}
+
+fn show(args: &args::PromptsShowArgs, config: &Config) -> Result<(), LibraryError> {
+    let prompt = config.prompts.0.iter()
+        .find(|prompt| prompt.name == args.name)
+        .ok_or_else(|| LibraryError::NotFound(args.name.clone()))?;
+    println!("# {}", config.origin(&format!("prompts[{}]", prompt.name)));
+    print!("{}", serde_yaml::to_string(prompt)?);
+    Ok(())
+}
+
+fn new(args: &args::PromptsNewArgs, config: &Config) -> Result<(), LibraryError> {
+    if config.prompts.0.iter().any(|prompt| prompt.name == args.name) {
+        return Err(LibraryError::AlreadyExists(args.name.clone()));
+    }
+    let dir = prompts_dir();
+    let path = dir.join(&args.name).with_extension(if args.yaml { "yml" } else { "md" });
+    let io_error = |source| LibraryError::Io { path: path.clone(), source };
+    std::fs::create_dir_all(&dir).map_err(io_error)?;
+    std::fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, new_prompt_file(!args.yaml).as_bytes()))
+        .map_err(io_error)?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+/// Prompt from the yaml, with the name if not defined
+fn parse_yaml(content: &str, name: &str) -> Result<Prompt, serde_yaml::Error> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(content)?;
+    if value.is_null() {
+        value = serde_yaml::Value::Mapping(Default::default());
+    }
+    if let serde_yaml::Value::Mapping(mapping) = &mut value {
+        mapping.entry("name".into()).or_insert_with(|| name.into());
+    }
+    serde_yaml::from_value(value)
+}
+
+/// Prompt from the markdown, errors are the line, from 1, and the message
+pub fn parse_markdown(content: &str, name: &str) -> Result<Prompt, (usize, String)> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut start = 0;
+    let mut prompt = if lines.first().map(|line| line.trim_end()) == Some(FRONT_MATTER_DELIMITER) {
+        let end = lines.iter().skip(1).position(|line| line.trim_end() == FRONT_MATTER_DELIMITER)
+            .ok_or((1, "the front-matter is not closed by `---`".to_string()))? + 1;
+        start = end + 1;
+        parse_yaml(&lines[1..end].join("\n"), name).map_err(|e| (1, format!("invalid front-matter: {}", e)))?
+    } else {
+        Prompt { name: name.to_string(), ..Default::default() }
+    };
+    let mut message: Option<(Role, Vec<&str>)> = None;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        if let Some(role) = section_role(line) {
+            prompt.messages.extend(message.take().map(to_message));
+            message = Some((role, Vec::new()));
+            continue;
+        }
+        match &mut message {
+            Some((_, content)) => content.push(line),
+            None if line.trim().is_empty() => (),
+            None => return Err((index + 1, "expected a `## system`, `## user` or `## assistant` heading".to_string())),
+        }
+    }
+    prompt.messages.extend(message.map(to_message));
+    Ok(prompt)
+}
+
+/// Role of the `## <role>` heading, the other headings are part of the content
+fn section_role(line: &str) -> Option<Role> {
+    let heading = line.strip_prefix("## ")?.trim();
+    match heading.to_lowercase().as_str() {
+        "system" => Some(Role::System),
+        "user" => Some(Role::User),
+        "assistant" => Some(Role::Assistant),
+        _ => None,
+    }
+}
+
+fn to_message((role, lines): (Role, Vec<&str>)) -> Message {
+    let content = lines.join("\n").trim().to_string();
+    Message {
+        role,
+        content: (!content.is_empty()).then_some(content),
+        fragment: None,
+    }
+}
+
+/// Content of a new prompt file, in markdown or in yaml
+pub fn new_prompt_file(markdown: bool) -> &'static str {
+    if markdown {
+        concat!(
+            "---\n",
+            "# extends: ask\n",
+            "parameters:\n",
+            "  temperature: 0.2\n",
+            "---\n",
+            "## system\n",
+            "You are a helpful assistant. Answer in markdown.\n",
+            "\n",
+            "## user\n",
+            "$input\n",
+            "\n",
+            "## assistant\n",
+        )
+    } else {
+        concat!(
+            "# extends: ask\n",
+            "messages:\n",
+            "  - role: system\n",
+            "    content: You are a helpful assistant. Answer in markdown.\n",
+            "  - role: user\n",
+            "    content: $input\n",
+            "  - role: assistant\n",
+            "    content: null\n",
+            "parameters:\n",
+            "  temperature: 0.2\n",
+        )
+    }
+}
@@ -1,52 +1,151 @@
+pub mod check;
+pub mod layers;
+pub mod library;
 pub mod prompt;
+pub mod schema;
+pub mod template;
+pub mod variables;
 
 
-use std::{borrow::Cow, collections::HashMap};
+use std::borrow::Cow;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
-use prompt::Prompts as PromptsConfig;
+use prompt::{Fragments, Prompts as PromptsConfig};
+use variables::{Variables, VariableError, Config as VariablesConfig};
+use crate::args;
 use crate::serde_io::DeserializeExt;
 #[cfg(feature = "local-llm")]
 use crate::generators::llama::config::Config as LlamaConfig;
+use crate::runner::config::Config as RunnerConfig;
+use crate::formatters::markdown::config::Config as MarkdownConfig;
+use crate::formatters::markdown::theme::Config as ThemeConfig;
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub prompts: PromptsConfig,
+    /// Contents shared by the messages of the prompts
+    #[serde(default, skip_serializing_if = "Fragments::is_empty")]
+    pub fragments: Fragments,
+    /// Variables of the prompts
+    #[serde(default, skip_serializing_if = "VariablesConfig::is_empty")]
+    pub variables: VariablesConfig,
     #[cfg(feature = "local-llm")]
+    #[serde(default)]
     pub local: LlamaConfig,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub runner: RunnerConfig,
+    /// Unknown fields are errors instead of warnings
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
+    /// Layer of each value of the configuration, by path
+    #[serde(skip)]
+    pub origins: layers::Origins,
 }
 
 impl DeserializeExt for Config {}
 
-pub fn format_content<'a>(content: &'a str, args: &HashMap<String, String>) -> Cow<'a, str> {
+/// Substitute the `$name` variables of the content, `$$name` is written as `$name`
+pub fn format_content<'a>(content: &'a str, variables: &Variables) -> Result<Cow<'a, str>, VariableError> {
     static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?P<prefix>\$\$?)(?P<name>\w+)").expect("Failed to compile regex"));
-    RE.replace_all(content, |caps: &regex::Captures| {
+    let mut error = None;
+    let content = RE.replace_all(content, |caps: &regex::Captures| {
         let prefix = &caps["prefix"];
         if prefix == "$$" {
             return format!("${}", &caps["name"]);
         }
-        let name = &caps["name"];
-        args.get(name).cloned().unwrap_or_default()
-    })
+        variables::lookup(variables, &caps["name"]).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            String::new()
+        })
+    });
+    match error {
+        Some(error) => Err(error),
+        None => Ok(content),
+    }
 }
 
 impl Config {
+    /// Configuration merged from the system file, the user file at the path, the nearest project file
+    /// and the environment, with the prompts of the prompts directory
+    ///
+    /// The user file is created with the default configuration if it doesn't exist.
     pub fn from_yaml_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
-        let found_path = crate::filesystem::config_path(path.as_ref());
-        let config = match found_path {
-            Some(found_path) => {
-                <Self as DeserializeExt>::from_yaml_file(found_path).map_err(|e| e.to_string())?
-            }
-            None => {
-                use std::io::Write;
-                let default_config = Self::default();
-                let yaml = serde_yaml::to_string(&default_config).map_err(|e| e.to_string())?;
-                std::fs::File::create(path).unwrap().write_all(yaml.as_bytes()).map_err(|e| e.to_string())?;
-                default_config
+        let path = path.as_ref();
+        if crate::filesystem::config_path(path).is_none() {
+            Self::write_default(path).map_err(|e| e.to_string())?;
+        }
+        let mut layers = layers::Layers::default();
+        for layer_path in Self::layer_paths(path) {
+            layers.merge_file(&layer_path).map_err(|e| e.to_string())?;
+        }
+        if let Some(project_path) = Self::project_path() {
+            for key in layers.merge_project_file(&project_path).map_err(|e| e.to_string())? {
+                log::warn!("`{}` of `{}` is ignored, a project file can't set it", key, project_path.display());
             }
-        };
+        }
+        layers.merge_env(std::env::vars()).map_err(|e| e.to_string())?;
+        let (mut config, warnings) = check::deserialize(&layers).map_err(|e| e.to_string())?;
+        for warning in warnings {
+            log::warn!("{}", warning);
+        }
+        config.origins = layers.origins;
+        let library = library::load(&library::prompts_dir()).map_err(|e| e.to_string())?;
+        for (name, path) in library::merge(&mut config.prompts, library) {
+            config.origins.insert(format!("prompts[{}]", name), layers::Origin::File(path));
+        }
+        config.prompts.resolve(&config.fragments).map_err(|e| e.to_string())?;
         Ok(config)
     }
-}
\ No newline at end of file
+    /// Write the default configuration, with the schema next to it for the completion in the editors
+    fn write_default(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        let schema_path = path.with_file_name(schema::SCHEMA_FILE_NAME);
+        std::fs::write(&schema_path, serde_json::to_string_pretty(&schema::config_schema())?)?;
+        let yaml = serde_yaml::to_string(&Self::default())?;
+        std::fs::write(path, schema::header(schema::SCHEMA_FILE_NAME) + &yaml)?;
+        Ok(())
+    }
+    /// Existing files of the trusted layers: the system file and the user file
+    pub fn layer_paths(user_path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        [layers::system_path(), user_path.to_path_buf()].into_iter()
+            .filter_map(|path| crate::filesystem::config_path(&path).map(std::borrow::Cow::into_owned))
+            .collect()
+    }
+    /// Nearest project file of the working directory
+    pub fn project_path() -> Option<std::path::PathBuf> {
+        std::env::current_dir().ok().and_then(|cwd| layers::project_path(&cwd))
+    }
+    /// File or environment variable the value at the path comes from
+    pub fn origin(&self, path: &str) -> String {
+        layers::origin(&self.origins, path).map_or_else(|| "default".to_string(), ToString::to_string)
+    }
+}
+
+pub fn command(command: &args::ConfigCommands, config: &Config) -> Result<(), serde_yaml::Error> {
+    match command {
+        args::ConfigCommands::Show(args) => show(args, config),
+        args::ConfigCommands::Check | args::ConfigCommands::Schema(_) => unreachable!("Check and schema subcommands are handled before the configuration is loaded"),
+    }
+}
+
+/// Print the effective configuration, with the layer of each value
+fn show(args: &args::ConfigShowArgs, config: &Config) -> Result<(), serde_yaml::Error> {
+    if !args.origin {
+        print!("{}", serde_yaml::to_string(config)?);
+        return Ok(());
+    }
+    // written then parsed, the floats of the configuration are written as f32
+    let value: serde_yaml::Value = serde_yaml::from_str(&serde_yaml::to_string(config)?)?;
+    for (path, leaf) in layers::flatten(&value) {
+        let leaf = serde_json::to_string(leaf).unwrap_or_default();
+        println!("{}: {}  # {}", path, leaf, config.origin(&path));
+    }
+    Ok(())
+}
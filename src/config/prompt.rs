@@ -1,18 +1,89 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use thiserror::Error;
+use super::template::TemplateError;
+use super::variables::{Variables, VariableError};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error(transparent)]
+    Variable(#[from] VariableError),
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+}
+
+/// Error of the formatting of a message, from 1
+#[derive(Error, Debug)]
+#[error("prompt `{prompt}`, message {index}: {source}")]
+pub struct PromptError {
+    pub prompt: String,
+    pub index: usize,
+    pub source: FormatError,
+}
+
+/// Error of the inheritance or of the fragments of the prompts, when the configuration is loaded
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("prompt `{prompt}` extends the unknown prompt `{parent}`")]
+    UnknownParent { prompt: String, parent: String },
+    #[error("prompts extend each other: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    #[error("prompt `{prompt}`, message {index}: unknown fragment `{fragment}`")]
+    UnknownFragment { prompt: String, index: usize, fragment: String },
+    #[error("prompt `{prompt}`, message {index}: a message can't have both a content and a fragment")]
+    ContentAndFragment { prompt: String, index: usize },
+}
+
+/// Contents shared by the messages of the prompts, by name
+pub type Fragments = HashMap<String, String>;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Prompts(pub Vec<Prompt>);
 
 impl Prompts {
-    pub fn format_contents(mut self, args: &HashMap<String, String>) -> Self {
-        self.0.iter_mut().for_each(|v| { Prompt::format_contents_as_ref(v, args); });
-        self
+    pub fn format_contents(mut self, variables: &Variables) -> Result<Self, PromptError> {
+        self.format_contents_as_ref(variables)?;
+        Ok(self)
+    }
+    pub fn format_contents_as_ref(&mut self, variables: &Variables) -> Result<&mut Self, PromptError> {
+        for prompt in self.0.iter_mut() {
+            prompt.format_contents_as_ref(variables)?;
+        }
+        Ok(self)
     }
-    pub fn format_contents_as_ref(&mut self, args: &HashMap<String, String>) -> &mut Self {
-        self.0.iter_mut().for_each(|v| { Prompt::format_contents_as_ref(v, args); });
-        self
+    /// Replace the fragments by their content, then merge each prompt with the prompts it extends
+    pub fn resolve(&mut self, fragments: &Fragments) -> Result<(), ResolveError> {
+        for prompt in self.0.iter_mut() {
+            prompt.resolve_fragments(fragments)?;
+        }
+        let resolved = self.0.iter()
+            .map(|prompt| {
+                let ancestors = self.ancestors(prompt)?;
+                Ok(ancestors.into_iter().rev().cloned().reduce(|parent, child| child.inherit(parent)).unwrap_or_else(|| prompt.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.0 = resolved;
+        Ok(())
+    }
+    /// The prompt, then its parent, up to the prompt extending none
+    fn ancestors<'a>(&'a self, prompt: &'a Prompt) -> Result<Vec<&'a Prompt>, ResolveError> {
+        let mut ancestors = vec![prompt];
+        let mut current = prompt;
+        while let Some(parent_name) = &current.extends {
+            let parent = self.0.iter().find(|p| &p.name == parent_name).ok_or_else(|| ResolveError::UnknownParent {
+                prompt: current.name.clone(),
+                parent: parent_name.clone(),
+            })?;
+            if let Some(start) = ancestors.iter().position(|p| p.name == parent.name) {
+                let cycle = ancestors[start..].iter().chain([&parent]).map(|p| p.name.clone()).collect();
+                return Err(ResolveError::Cycle(cycle));
+            }
+            ancestors.push(parent);
+            current = parent;
+        }
+        Ok(ancestors)
     }
 }
 impl Default for Prompts {
@@ -20,18 +91,24 @@ impl Default for Prompts {
         Prompts(vec![
                 Prompt {
                     name: "command".to_string(),
+                    extends: None,
+                    messages_mode: None,
+                    template: false,
                     messages: vec![
                         Message {
                             role: Role::System,
-                            content: Some("In markdown, write the unix command that best fits my request in a block of code under a \"## Command\" then describe the program and each parameter in \"## Explanation\".".to_string()),
+                            content: Some("In markdown, write the $shell command for $os that best fits my request in a block of code under a \"## Command\" then describe the program and each parameter in \"## Explanation\".".to_string()),
+                            fragment: None,
                         },
                         Message {
                             role: Role::User,
                             content: Some("$input".to_string()),
+                            fragment: None,
                         },
                         Message {
                             role: Role::Assistant,
                             content: None,
+                            fragment: None,
                         },
                     ],
                     parameters: Parameters {
@@ -45,18 +122,24 @@ impl Default for Prompts {
                 },
                 Prompt {
                     name: "ask".to_string(),
+                    extends: None,
+                    messages_mode: None,
+                    template: false,
                     messages: vec![
                         Message {
                             role: Role::System,
                             content: Some("You are a powerful intelligent conversational chatbot. Unless I tell you otherwise, answer to me in an informative way. You should format the text in Markdown.".to_string()),
+                            fragment: None,
                         },
                         Message {
                             role: Role::User,
                             content: Some("$input".to_string()),
+                            fragment: None,
                         },
                         Message {
                             role: Role::Assistant,
                             content: None,
+                            fragment: None,
                         },
                     ],
                     parameters: Parameters {
@@ -72,10 +155,32 @@ impl Default for Prompts {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+/// How the messages of a prompt are merged with the ones of the prompt it extends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MessagesMode {
+    /// After the messages of the parent
+    #[default]
+    Append,
+    /// Instead of the messages of the parent, if any
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Prompt {
     pub name: String,
+    /// Name of the prompt whose messages and parameters are inherited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages_mode: Option<MessagesMode>,
+    /// Write the contents with the template syntax instead of the `$name` variables
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+    #[serde(default)]
     pub messages: Vec<Message>,
+    #[serde(default)]
     pub parameters: Parameters,
 }
 
@@ -86,24 +191,67 @@ impl Prompt {
             messages: vec![Message {
                 role: Role::User,
                 content: Some(input.into()),
+                fragment: None,
             }],
             ..Default::default()
         }
     }
-    pub fn format_contents(mut self, args: &HashMap<String, String>) -> Self {
-        self.messages.iter_mut().for_each(|m|{ m.format_content_as_ref(args); });
-        self
+    pub fn format_contents(mut self, variables: &Variables) -> Result<Self, PromptError> {
+        self.format_contents_as_ref(variables)?;
+        Ok(self)
     }
-    pub fn format_contents_as_ref(&mut self, args: &HashMap<String, String>) -> &mut Self {
-        self.messages.iter_mut().for_each(|m| { m.format_content_as_ref(args); });
-        self
+    pub fn format_contents_as_ref(&mut self, variables: &Variables) -> Result<&mut Self, PromptError> {
+        for (index, message) in self.messages.iter_mut().enumerate() {
+            Self::format_message(&self.name, self.template, index, message, variables)?;
+        }
+        Ok(self)
+    }
+    pub fn formatted_messages(&self, variables: &Variables) -> Result<Vec<Message>, PromptError> {
+        self.messages.iter().cloned().enumerate()
+            .map(|(index, mut message)| {
+                Self::format_message(&self.name, self.template, index, &mut message, variables)?;
+                Ok(message)
+            })
+            .collect()
     }
-    pub fn formatted_messages(&self, args: &HashMap<String, String>) -> Vec<Message> {
-        self.messages.iter().cloned().map(|v| Message::format_content(v, args)).collect()
+    fn resolve_fragments(&mut self, fragments: &Fragments) -> Result<(), ResolveError> {
+        for (index, message) in self.messages.iter_mut().enumerate() {
+            let Some(fragment) = message.fragment.take() else { continue };
+            if message.content.is_some() {
+                return Err(ResolveError::ContentAndFragment { prompt: self.name.clone(), index: index + 1 });
+            }
+            let content = fragments.get(&fragment).ok_or_else(|| ResolveError::UnknownFragment {
+                prompt: self.name.clone(),
+                index: index + 1,
+                fragment: fragment.clone(),
+            })?;
+            message.content = Some(content.clone());
+        }
+        Ok(())
+    }
+    /// Merge the prompt with its parent, already merged with its own parents
+    fn inherit(self, parent: Prompt) -> Self {
+        let messages = match self.messages_mode.unwrap_or_default() {
+            MessagesMode::Replace if !self.messages.is_empty() => self.messages,
+            _ => parent.messages.into_iter().chain(self.messages).collect(),
+        };
+        Self {
+            messages,
+            template: self.template || parent.template,
+            parameters: self.parameters.inherit(parent.parameters),
+            ..self
+        }
+    }
+    fn format_message(name: &str, template: bool, index: usize, message: &mut Message, variables: &Variables) -> Result<(), PromptError> {
+        let result = match template {
+            true => message.render_template_as_ref(variables).map(|_| ()).map_err(FormatError::from),
+            false => message.format_content_as_ref(variables).map(|_| ()).map_err(FormatError::from),
+        };
+        result.map_err(|source| PromptError { prompt: name.to_string(), index: index + 1, source })
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     User,
@@ -130,25 +278,37 @@ impl Role {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Message {
     pub role: Role,
     pub content: Option<String>,
+    /// Name of the fragment used as content, replaced by its content once the configuration is loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<String>,
 }
 
 #[allow(dead_code)]
 impl Message {
-    pub fn format_content(mut self, args: &HashMap<String, String>) -> Self {
-        self.content = self.content.map(|c| crate::config::format_content(&c, args).to_string());
-        self
+    pub fn format_content(mut self, variables: &Variables) -> Result<Self, VariableError> {
+        self.format_content_as_ref(variables)?;
+        Ok(self)
     }
-    pub fn format_content_as_ref(&mut self, args: &HashMap<String, String>) -> &mut Self {
-        self.content = self.content.as_mut().map(|c| crate::config::format_content(c, args).to_string());
-        self
+    pub fn format_content_as_ref(&mut self, variables: &Variables) -> Result<&mut Self, VariableError> {
+        if let Some(content) = self.content.as_mut() {
+            *content = crate::config::format_content(content, variables)?.into_owned();
+        }
+        Ok(self)
+    }
+    pub fn render_template_as_ref(&mut self, variables: &Variables) -> Result<&mut Self, TemplateError> {
+        if let Some(content) = self.content.as_mut() {
+            *content = super::template::render(content, variables)?;
+        }
+        Ok(self)
     }
 }
 #[cfg(feature = "local-llm")]
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum Algorithm {
     SoftMax{
         min_keep: usize,
@@ -166,6 +326,7 @@ pub enum Algorithm {
         eta: f32,
     },
 }
+#[cfg(feature = "local-llm")]
 impl Default for Algorithm {
     fn default() -> Self {
         Algorithm::MirostatV2 { 
@@ -176,8 +337,9 @@ impl Default for Algorithm {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
 pub struct Parameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
@@ -214,8 +376,35 @@ pub struct Parameters {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub min_p: Option<f32>,
     #[cfg(feature = "local-llm")]
-    #[serde(default)]
-    pub algorithm: Algorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<Algorithm>,
+}
+
+impl Parameters {
+    /// Parameters not set are taken from the parent
+    pub fn inherit(self, parent: Parameters) -> Self {
+        Self {
+            max_tokens: self.max_tokens.or(parent.max_tokens),
+            temperature: self.temperature.or(parent.temperature),
+            top_p: self.top_p.or(parent.top_p),
+            presence_penalty: self.presence_penalty.or(parent.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(parent.frequency_penalty),
+            stop: if self.stop.is_none() { parent.stop } else { self.stop },
+            n: self.n.or(parent.n),
+            #[cfg(feature = "local-llm")]
+            last_n: self.last_n.or(parent.last_n),
+            #[cfg(feature = "local-llm")]
+            top_k: self.top_k.or(parent.top_k),
+            #[cfg(feature = "local-llm")]
+            tail_free: self.tail_free.or(parent.tail_free),
+            #[cfg(feature = "local-llm")]
+            typical: self.typical.or(parent.typical),
+            #[cfg(feature = "local-llm")]
+            min_p: self.min_p.or(parent.min_p),
+            #[cfg(feature = "local-llm")]
+            algorithm: self.algorithm.or(parent.algorithm),
+        }
+    }
 }
 
 #[cfg(feature = "local-llm")]
@@ -249,7 +438,7 @@ impl From<Parameters> for llama_cpp::standard_sampler::StandardSampler {
         if let Some(min_p) = parameters.min_p {
             stages.push(SamplerStage::MinP(min_p));
         }
-        match parameters.algorithm {
+        match parameters.algorithm.unwrap_or_default() {
             Algorithm::SoftMax { min_keep } => Self::new_softmax(stages, min_keep),
             Algorithm::Greedy => Self::new_greedy(),
             Algorithm::Mirostat { min_keep, tau, eta, m } => Self::new_mirostat(stages, min_keep, tau, eta, m),
@@ -258,7 +447,7 @@ impl From<Parameters> for llama_cpp::standard_sampler::StandardSampler {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub enum Stop {
     #[default]
     None,
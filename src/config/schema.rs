@@ -0,0 +1,31 @@
+//! JSON Schema of the configuration and credentials files, for the editors with a YAML language server
+
+use schemars::schema::RootSchema;
+use super::Config;
+use crate::args;
+use crate::credentials::Credentials;
+
+/// Name of the schema file written next to the default configuration file
+pub const SCHEMA_FILE_NAME: &str = "config.schema.json";
+
+pub fn config_schema() -> RootSchema {
+    schemars::schema_for!(Config)
+}
+
+pub fn credentials_schema() -> RootSchema {
+    schemars::schema_for!(Credentials)
+}
+
+/// First line of a yaml file, associating it with the schema for the YAML language server
+pub fn header(schema: &str) -> String {
+    format!("# yaml-language-server: $schema={}\n", schema)
+}
+
+pub fn command(args: &args::ConfigSchemaArgs) -> Result<(), serde_json::Error> {
+    let schema = match args.creds {
+        true => credentials_schema(),
+        false => config_schema(),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
@@ -0,0 +1,360 @@
+//! Template syntax of the prompt messages
+//!
+//! `{{ name | default("x") }}` writes a variable, `{% if %}`/`{% elif %}`/`{% else %}`/`{% endif %}`
+//! and `{% for item in name %}`/`{% endfor %}` control the text, and `{% include "file" %}` writes a
+//! template file of the configuration directory,
+//! the files outside of it can't be included. The newline following a tag is removed.
+
+use std::path::{Path, PathBuf};
+use super::variables::{self, Variables, VariableError};
+use thiserror::Error;
+
+/// Deepest nesting of the included files
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: {source}")]
+    Variable { line: usize, source: VariableError },
+    #[error("line {line}: failed to read `{path}`: {source}")]
+    ReadFile { line: usize, path: String, source: std::io::Error },
+    #[error("line {line}: `{path}` is outside of the configuration directory")]
+    OutsideDir { line: usize, path: String },
+    #[error("line {line}: `{path}` is included too deeply, the includes may be recursive")]
+    TooDeep { line: usize, path: String },
+    #[error("in `{path}`, {source}")]
+    InFile { path: String, source: Box<TemplateError> },
+}
+
+fn syntax_error(line: usize, message: impl Into<String>) -> TemplateError {
+    TemplateError::Syntax { line, message: message.into() }
+}
+
+enum Token {
+    Text(String),
+    Expression(String),
+    Tag(String),
+}
+
+/// Split the template into text, `{{ expression }}` and `{% tag %}`, with their line
+fn tokenize(template: &str) -> Result<Vec<(Token, usize)>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    let mut line = 1;
+    let mut after_tag = false;
+    while !rest.is_empty() {
+        let start = rest.find("{{").into_iter().chain(rest.find("{%")).min().unwrap_or(rest.len());
+        let mut text = &rest[..start];
+        if after_tag {
+            text = text.strip_prefix("\r\n").or_else(|| text.strip_prefix('\n')).unwrap_or(text);
+        }
+        if !text.is_empty() {
+            tokens.push((Token::Text(text.to_string()), line));
+        }
+        line += rest[..start].matches('\n').count();
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        let (is_tag, end_marker) = if rest.starts_with("{%") { (true, "%}") } else { (false, "}}") };
+        let Some(end) = rest.find(end_marker) else {
+            return Err(syntax_error(line, format!("`{}` is not closed", &rest[..2])));
+        };
+        let content = rest[2..end].trim().to_string();
+        tokens.push((if is_tag { Token::Tag(content) } else { Token::Expression(content) }, line));
+        after_tag = is_tag;
+        line += rest[..end].matches('\n').count();
+        rest = &rest[end + 2..];
+    }
+    Ok(tokens)
+}
+
+/// Split the text on the separator, except inside the string literals
+fn split_outside_quotes<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if text[i..].starts_with(separator) && i >= start => {
+                parts.push(&text[start..i]);
+                start = i + separator.len();
+            }
+            None => (),
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn parse_literal(text: &str) -> Option<String> {
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    text.strip_prefix(quote)?.strip_suffix(quote).map(str::to_string)
+}
+
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+enum Operand {
+    Variable(String),
+    Literal(String),
+}
+
+enum Filter {
+    Default(String),
+    Upper,
+    Lower,
+    Trim,
+}
+
+struct Expression {
+    operand: Operand,
+    filters: Vec<Filter>,
+}
+
+impl Expression {
+    fn parse(text: &str, line: usize) -> Result<Self, TemplateError> {
+        let mut parts = split_outside_quotes(text, "|").into_iter().map(str::trim);
+        let operand = parts.next().unwrap_or_default();
+        let operand = match parse_literal(operand) {
+            Some(literal) => Operand::Literal(literal),
+            None if is_identifier(operand) => Operand::Variable(operand.to_string()),
+            None => return Err(syntax_error(line, format!("invalid expression `{}`", text))),
+        };
+        let filters = parts.map(|filter| {
+            let (name, argument) = match filter.split_once('(') {
+                Some((name, argument)) => (name.trim(), argument.strip_suffix(')').map(str::trim)),
+                None => (filter, None),
+            };
+            match (name, argument.map(parse_literal)) {
+                ("default", Some(Some(value))) => Ok(Filter::Default(value)),
+                ("upper", None) => Ok(Filter::Upper),
+                ("lower", None) => Ok(Filter::Lower),
+                ("trim", None) => Ok(Filter::Trim),
+                _ => Err(syntax_error(line, format!("invalid filter `{}`", filter))),
+            }
+        }).collect::<Result<_, _>>()?;
+        Ok(Self { operand, filters })
+    }
+}
+
+enum Condition {
+    /// Whether the value is defined and not empty
+    Truthy(Expression),
+    Not(Box<Condition>),
+    Equals(Expression, Expression),
+    NotEquals(Expression, Expression),
+}
+
+impl Condition {
+    fn parse(text: &str, line: usize) -> Result<Self, TemplateError> {
+        if let Some(rest) = text.strip_prefix("not ") {
+            return Ok(Self::Not(Box::new(Self::parse(rest.trim(), line)?)));
+        }
+        for (operator, condition) in [("==", Self::Equals as fn(_, _) -> _), ("!=", Self::NotEquals)] {
+            if let [left, right] = split_outside_quotes(text, operator)[..] {
+                return Ok(condition(Expression::parse(left.trim(), line)?, Expression::parse(right.trim(), line)?));
+            }
+        }
+        Ok(Self::Truthy(Expression::parse(text, line)?))
+    }
+}
+
+enum Node {
+    Text(String),
+    Expression(Expression, usize),
+    If { branches: Vec<(Condition, Vec<Node>)>, otherwise: Vec<Node> },
+    For { name: String, items: Expression, body: Vec<Node>, line: usize },
+    Include(String, usize),
+}
+
+/// Tag ending a block, with its line
+struct EndTag {
+    keyword: String,
+    argument: String,
+    line: usize,
+}
+
+struct Parser {
+    tokens: std::vec::IntoIter<(Token, usize)>,
+}
+
+impl Parser {
+    /// Parse the nodes until one of the end tags of the block opened at the line
+    fn parse_block(&mut self, end_keywords: &[&str], opening_line: usize) -> Result<(Vec<Node>, Option<EndTag>), TemplateError> {
+        let mut nodes = Vec::new();
+        while let Some((token, line)) = self.tokens.next() {
+            let tag = match token {
+                Token::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Token::Expression(expression) => {
+                    nodes.push(Node::Expression(Expression::parse(&expression, line)?, line));
+                    continue;
+                }
+                Token::Tag(tag) => tag,
+            };
+            let (keyword, argument) = tag.split_once(char::is_whitespace).unwrap_or((&tag, ""));
+            let argument = argument.trim();
+            if end_keywords.contains(&keyword) {
+                return Ok((nodes, Some(EndTag { keyword: keyword.to_string(), argument: argument.to_string(), line })));
+            }
+            nodes.push(match keyword {
+                "if" => self.parse_if(argument, line)?,
+                "for" => self.parse_for(argument, line)?,
+                "include" => {
+                    let path = parse_literal(argument).ok_or_else(|| syntax_error(line, "the path of `include` must be a string"))?;
+                    Node::Include(path, line)
+                }
+                _ => return Err(syntax_error(line, format!("unexpected tag `{{% {} %}}`", tag))),
+            });
+        }
+        match end_keywords.last() {
+            Some(keyword) => Err(syntax_error(opening_line, format!("`{{% {} %}}` is missing", keyword))),
+            None => Ok((nodes, None)),
+        }
+    }
+    fn parse_if(&mut self, condition: &str, line: usize) -> Result<Node, TemplateError> {
+        let mut branches = Vec::new();
+        let mut condition = Condition::parse(condition, line)?;
+        loop {
+            let (body, end) = self.parse_block(&["elif", "else", "endif"], line)?;
+            branches.push((condition, body));
+            let Some(end) = end else { unreachable!("the end tag is checked by parse_block") };
+            match end.keyword.as_str() {
+                "elif" => condition = Condition::parse(&end.argument, end.line)?,
+                "else" => {
+                    let (otherwise, _) = self.parse_block(&["endif"], line)?;
+                    return Ok(Node::If { branches, otherwise });
+                }
+                _ => return Ok(Node::If { branches, otherwise: Vec::new() }),
+            }
+        }
+    }
+    fn parse_for(&mut self, argument: &str, line: usize) -> Result<Node, TemplateError> {
+        let Some((name, items)) = argument.split_once(" in ").filter(|(name, _)| is_identifier(name.trim())) else {
+            return Err(syntax_error(line, "expected `{% for item in name %}`"));
+        };
+        let items = Expression::parse(items.trim(), line)?;
+        let (body, _) = self.parse_block(&["endfor"], line)?;
+        Ok(Node::For { name: name.trim().to_string(), items, body, line })
+    }
+}
+
+fn parse(template: &str) -> Result<Vec<Node>, TemplateError> {
+    let mut parser = Parser { tokens: tokenize(template)?.into_iter() };
+    Ok(parser.parse_block(&[], 1)?.0)
+}
+
+struct Context<'a> {
+    variables: &'a Variables,
+    /// Variables of the loops, the innermost last
+    scopes: Vec<(String, String)>,
+    depth: usize,
+    /// Directory of the included files
+    include_dir: &'a Path,
+}
+
+impl Context<'_> {
+    /// Value of the expression, None if its variable is not defined
+    fn evaluate(&self, expression: &Expression) -> Option<String> {
+        let mut value = match &expression.operand {
+            Operand::Literal(literal) => Some(literal.clone()),
+            Operand::Variable(name) => self.scopes.iter().rev()
+                .find(|(scope_name, _)| scope_name == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| variables::lookup(self.variables, name).ok()),
+        };
+        for filter in &expression.filters {
+            value = match (filter, value) {
+                (Filter::Default(default), None) => Some(default.clone()),
+                (_, None) => None,
+                (Filter::Default(_), value) => value,
+                (Filter::Upper, Some(value)) => Some(value.to_uppercase()),
+                (Filter::Lower, Some(value)) => Some(value.to_lowercase()),
+                (Filter::Trim, Some(value)) => Some(value.trim().to_string()),
+            };
+        }
+        value
+    }
+    fn evaluate_defined(&self, expression: &Expression, line: usize) -> Result<String, TemplateError> {
+        self.evaluate(expression).ok_or_else(|| {
+            let Operand::Variable(name) = &expression.operand else { unreachable!("a literal is always defined") };
+            let source = variables::lookup(self.variables, name).err().unwrap_or_else(|| VariableError::Missing(name.clone()));
+            TemplateError::Variable { line, source }
+        })
+    }
+    fn check(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Truthy(expression) => self.evaluate(expression).is_some_and(|value| !value.is_empty()),
+            Condition::Not(condition) => !self.check(condition),
+            Condition::Equals(left, right) => self.evaluate(left).unwrap_or_default() == self.evaluate(right).unwrap_or_default(),
+            Condition::NotEquals(left, right) => self.evaluate(left).unwrap_or_default() != self.evaluate(right).unwrap_or_default(),
+        }
+    }
+    fn render(&mut self, nodes: &[Node], output: &mut String) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Expression(expression, line) => output.push_str(&self.evaluate_defined(expression, *line)?),
+                Node::If { branches, otherwise } => {
+                    let body = branches.iter()
+                        .find(|(condition, _)| self.check(condition))
+                        .map_or(otherwise, |(_, body)| body);
+                    self.render(body, output)?;
+                }
+                Node::For { name, items, body, line } => {
+                    // the items are the lines of the value
+                    for item in self.evaluate_defined(items, *line)?.lines().filter(|item| !item.trim().is_empty()) {
+                        self.scopes.push((name.clone(), item.to_string()));
+                        let result = self.render(body, output);
+                        self.scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Include(path, line) => self.include(path, *line, output)?,
+            }
+        }
+        Ok(())
+    }
+    fn include(&mut self, path: &str, line: usize, output: &mut String) -> Result<(), TemplateError> {
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(TemplateError::TooDeep { line, path: path.to_string() });
+        }
+        let read_error = |source| TemplateError::ReadFile { line, path: path.to_string(), source };
+        let full_path = include_path(self.include_dir, path).map_err(read_error)?
+            .ok_or_else(|| TemplateError::OutsideDir { line, path: path.to_string() })?;
+        let template = std::fs::read_to_string(full_path).map_err(read_error)?;
+        self.depth += 1;
+        let result = parse(&template).and_then(|nodes| self.render(&nodes, output));
+        self.depth -= 1;
+        result.map_err(|source| TemplateError::InFile { path: path.to_string(), source: Box::new(source) })
+    }
+}
+
+/// Path of the included file, `None` if it is outside of the directory (absolute path, `..`, symbolic link)
+pub fn include_path(dir: &Path, path: &str) -> std::io::Result<Option<PathBuf>> {
+    let dir = dir.canonicalize()?;
+    let full_path = dir.join(path).canonicalize()?;
+    Ok(full_path.starts_with(&dir).then_some(full_path))
+}
+
+/// Render the template with the variables, the files are included from the configuration directory
+pub fn render(template: &str, variables: &Variables) -> Result<String, TemplateError> {
+    render_in(template, variables, Path::new(crate::filesystem::config_dir()))
+}
+
+/// Render the template with the variables, the files are included from the directory
+pub fn render_in(template: &str, variables: &Variables, include_dir: &Path) -> Result<String, TemplateError> {
+    let nodes = parse(template)?;
+    let mut output = String::with_capacity(template.len());
+    Context { variables, scopes: Vec::new(), depth: 0, include_dir }.render(&nodes, &mut output)?;
+    Ok(output)
+}
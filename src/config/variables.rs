@@ -0,0 +1,103 @@
+//! Variables substituted in the messages of the prompts
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use thiserror::Error;
+use crate::{args, filesystem};
+
+/// Values of the `$name` variables, by name
+pub type Variables = HashMap<String, String>;
+
+/// Prefix of the variables read from the environment (ex: `$env_HOME`)
+pub const ENV_PREFIX: &str = "env_";
+
+/// Variables section of the configuration
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Environment variables the prompts can read as `$env_NAME`, the other ones are not readable
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+impl Config {
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VariableError {
+    #[error("variable `${0}` is not defined")]
+    Missing(String),
+    #[error("variable `${ENV_PREFIX}{0}` is not readable, the environment variable must be listed in `variables.env`")]
+    EnvNotAllowed(String),
+    #[error("expected `NAME=VALUE`, got `{0}`")]
+    InvalidDefinition(String),
+    #[error("failed to read the file of the variable `${name}`: {source}")]
+    ReadFile { name: String, source: std::io::Error },
+}
+
+/// Parse the definition of a variable from the command line
+pub fn parse_definition(definition: &str) -> Result<(String, String), VariableError> {
+    match definition.split_once('=') {
+        Some((name, value)) if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            Ok((name.to_string(), value.to_string()))
+        }
+        _ => Err(VariableError::InvalidDefinition(definition.to_string())),
+    }
+}
+
+/// Variables describing the environment of aio: `$os`, `$shell`, `$cwd`, `$date` and `$user`
+pub fn builtins() -> Variables {
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let mut variables = Variables::new();
+    variables.insert("os".into(), std::env::consts::OS.into());
+    let shell = env("SHELL").or_else(|| env("ComSpec"))
+        .and_then(|path| std::path::Path::new(&path).file_stem().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "sh".into());
+    variables.insert("shell".into(), shell);
+    if let Ok(cwd) = std::env::current_dir() {
+        variables.insert("cwd".into(), cwd.display().to_string());
+    }
+    variables.insert("date".into(), chrono::Local::now().format("%Y-%m-%d").to_string());
+    if let Some(user) = env("USER").or_else(|| env("USERNAME")) {
+        variables.insert("user".into(), user);
+    }
+    variables
+}
+
+/// Variables of the prompts: the built-ins, then the `--var-file` and `--var` arguments, and `$input`
+pub fn from_args(args: &args::Args) -> Result<Variables, VariableError> {
+    let mut variables = builtins();
+    for (name, path) in &args.var_files {
+        let content = std::fs::read_to_string(filesystem::resolve_path(path).as_ref())
+            .map_err(|source| VariableError::ReadFile { name: name.clone(), source })?;
+        variables.insert(name.clone(), content.trim_end_matches(['\r', '\n']).to_string());
+    }
+    variables.extend(args.vars.iter().cloned());
+    variables.insert("input".into(), args.input.clone());
+    Ok(variables)
+}
+
+/// Add the `$env_NAME` variables of the allowed environment variables which are set,
+/// without replacing the ones already defined
+pub fn add_env(variables: &mut Variables, config: &Config) {
+    for name in &config.env {
+        if let Ok(value) = std::env::var(name) {
+            variables.entry(format!("{}{}", ENV_PREFIX, name)).or_insert(value);
+        }
+    }
+}
+
+/// Value of the variable
+pub fn lookup(variables: &Variables, name: &str) -> Result<String, VariableError> {
+    if let Some(value) = variables.get(name) {
+        return Ok(value.clone());
+    }
+    match name.strip_prefix(ENV_PREFIX) {
+        Some(env_name) if std::env::var_os(env_name).is_some() => Err(VariableError::EnvNotAllowed(env_name.to_string())),
+        _ => Err(VariableError::Missing(name.to_string())),
+    }
+}
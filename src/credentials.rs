@@ -1,12 +1,14 @@
 use serde::Deserialize;
+use schemars::JsonSchema;
 use crate::{
     serde_io::DeserializeExt,
     generators::openai::credentials::Credentials as CredsOpenAI
 };
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Credentials {
+    /// Credentials of the OpenAI API
     pub openai: CredsOpenAI
 }
 
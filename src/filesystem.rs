@@ -43,7 +43,7 @@ pub fn cache_dir() -> &'static str {
     &CACHE
 }
 
-pub fn resolve_path(path: &str) -> Cow<str> {
+pub fn resolve_path(path: &str) -> Cow<'_, str> {
     if let Some(path) = path.strip_prefix("~/") {
         Cow::Owned(format!("{}{}{}", home_dir(), std::path::MAIN_SEPARATOR, path))
     } else {
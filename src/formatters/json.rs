@@ -0,0 +1,133 @@
+use super::Formatter;
+use super::markdown::{self, token::Token, Renderer};
+use crate::generators;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Line of the output of the json formatter
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// Next part of the completion, as received from the engine
+    Text { text: String },
+    /// Markdown token parsed from the completion
+    Token { token: Token },
+    CodeBlock { language: String, code: String },
+    /// Execution of a code block by the runner
+    Run {
+        language: String,
+        interpreter: String,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        stdout: String,
+        stderr: String,
+    },
+    Usage(generators::Usage),
+    Finish { reason: String },
+    Error { message: String },
+}
+
+/// Write the event as one line of json
+pub fn write_event(out: &mut impl Write, event: &Event) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *out, event)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+pub fn print_event(event: &Event) -> std::io::Result<()> {
+    write_event(&mut std::io::stdout().lock(), event)
+}
+
+struct CodeBlock {
+    language: String,
+    code: String,
+    /// Whether the line of the language is ended
+    started: bool,
+}
+
+/// Renderer writing the tokens as json events, and the code blocks once complete
+pub struct JsonRenderer<W: Write> {
+    out: W,
+    code: Option<CodeBlock>,
+}
+
+impl<W: Write> JsonRenderer<W> {
+    fn write_event(&mut self, event: &Event) -> std::io::Result<()> {
+        write_event(&mut self.out, event)
+    }
+}
+
+impl<W: Write> Renderer for JsonRenderer<W> {
+    type Error = std::io::Error;
+    fn push_token(&mut self, token: Token) -> Result<(), Self::Error> {
+        match (&token, self.code.as_mut()) {
+            (Token::BeginCode, _) => {
+                self.code = Some(CodeBlock { language: String::new(), code: String::new(), started: false });
+            }
+            (Token::Text(s), Some(code)) if !code.started => code.language.push_str(s),
+            (Token::Text(s), Some(code)) => code.code.push_str(s),
+            (Token::Newline, Some(code)) if !code.started => code.started = true,
+            (Token::Newline, Some(code)) => code.code.push('\n'),
+            (Token::EndCode | Token::EndDocument, Some(_)) => {
+                let Some(code) = self.code.take() else { unreachable!() };
+                self.write_event(&Event::CodeBlock {
+                    language: code.language.trim().to_string(),
+                    // the last newline is before the fence
+                    code: code.code.strip_suffix('\n').unwrap_or(&code.code).to_string(),
+                })?;
+            }
+            _ => (),
+        }
+        self.write_event(&Event::Token { token })
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.out.flush()
+    }
+}
+
+/// Formatter writing newline-delimited json events, for the programs wrapping aio
+pub struct JsonFormatter<W: Write = std::io::Stdout> {
+    parser: markdown::Parser<JsonRenderer<W>>,
+}
+
+impl Default for JsonFormatter {
+    fn default() -> Self {
+        Self::with_output(std::io::stdout())
+    }
+}
+
+impl<W: Write> JsonFormatter<W> {
+    pub fn with_output(out: W) -> Self {
+        Self {
+            parser: markdown::Parser::new(JsonRenderer { out, code: None }),
+        }
+    }
+    pub fn into_output(self) -> W {
+        self.parser.into_renderer().out
+    }
+}
+
+impl<W: Write> Formatter for JsonFormatter<W> {
+    fn push(&mut self, text: &str) -> Result<()> {
+        self.parser.renderer_mut().write_event(&Event::Text { text: text.to_string() })?;
+        self.parser.push(text)
+    }
+    fn push_event(&mut self, event: &generators::Event) -> Result<()> {
+        let event = match event {
+            generators::Event::Text(text) => return self.push(text),
+            generators::Event::Usage(usage) => Event::Usage(usage.clone()),
+            generators::Event::Finish(reason) => Event::Finish { reason: reason.clone() },
+        };
+        Ok(self.parser.renderer_mut().write_event(&event)?)
+    }
+    fn end_of_document(&mut self) -> Result<()> {
+        self.parser.end_of_document()
+    }
+    fn cancel(&mut self) -> Result<()> {
+        self.push_event(&generators::Event::Finish("cancelled".to_string()))?;
+        // like the runner, the code block interrupted by the cancellation is dropped
+        self.parser.renderer_mut().code = None;
+        self.end_of_document()
+    }
+}
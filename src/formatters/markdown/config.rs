@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Highlight the code blocks with the syntax of their language
+    pub syntax_highlighting: bool,
+    /// Name of the highlighting theme
+    pub syntax_theme: String,
+    /// Page the answers which don't fit in the terminal
+    pub pager: bool,
+    /// Command of the pager, `$PAGER` or `less` if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pager_command: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            syntax_highlighting: true,
+            syntax_theme: "base16-ocean.dark".to_string(),
+            pager: false,
+            pager_command: None,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Conversion of simple LaTeX math to unicode text
+
+const SYMBOLS: [(&str, &str); 66] = [
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ϵ"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"),
+    ("sigma", "σ"), ("tau", "τ"), ("upsilon", "υ"), ("phi", "ϕ"), ("varphi", "φ"), ("chi", "χ"),
+    ("psi", "ψ"), ("omega", "ω"),
+    ("Gamma", "Γ"), ("Delta", "Δ"), ("Theta", "Θ"), ("Lambda", "Λ"), ("Xi", "Ξ"), ("Pi", "Π"),
+    ("Sigma", "Σ"), ("Upsilon", "Υ"), ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("infty", "∞"), ("pm", "±"), ("mp", "∓"), ("times", "×"), ("div", "÷"), ("cdot", "·"),
+    ("leq", "≤"), ("le", "≤"), ("geq", "≥"), ("ge", "≥"), ("neq", "≠"), ("ne", "≠"), ("approx", "≈"),
+    ("equiv", "≡"), ("to", "→"), ("rightarrow", "→"), ("leftarrow", "←"), ("Rightarrow", "⇒"),
+    ("Leftrightarrow", "⇔"), ("sum", "∑"), ("prod", "∏"), ("int", "∫"), ("partial", "∂"),
+    ("nabla", "∇"), ("in", "∈"), ("forall", "∀"), ("exists", "∃"), ("ldots", "…"), ("cdots", "⋯"),
+];
+
+/// Commands whose argument is written as is
+const TEXT_COMMANDS: [&str; 6] = ["text", "mathrm", "mathbf", "mathit", "mathsf", "operatorname"];
+
+fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰', '1' => '¹', '2' => '²', '3' => '³', '4' => '⁴',
+        '5' => '⁵', '6' => '⁶', '7' => '⁷', '8' => '⁸', '9' => '⁹',
+        '+' => '⁺', '-' => '⁻', '=' => '⁼', '(' => '⁽', ')' => '⁾',
+        'a' => 'ᵃ', 'b' => 'ᵇ', 'c' => 'ᶜ', 'd' => 'ᵈ', 'e' => 'ᵉ', 'f' => 'ᶠ', 'g' => 'ᵍ',
+        'h' => 'ʰ', 'i' => 'ⁱ', 'j' => 'ʲ', 'k' => 'ᵏ', 'l' => 'ˡ', 'm' => 'ᵐ', 'n' => 'ⁿ',
+        'o' => 'ᵒ', 'p' => 'ᵖ', 'r' => 'ʳ', 's' => 'ˢ', 't' => 'ᵗ', 'u' => 'ᵘ', 'v' => 'ᵛ',
+        'w' => 'ʷ', 'x' => 'ˣ', 'y' => 'ʸ', 'z' => 'ᶻ',
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀', '1' => '₁', '2' => '₂', '3' => '₃', '4' => '₄',
+        '5' => '₅', '6' => '₆', '7' => '₇', '8' => '₈', '9' => '₉',
+        '+' => '₊', '-' => '₋', '=' => '₌', '(' => '₍', ')' => '₎',
+        'a' => 'ₐ', 'e' => 'ₑ', 'h' => 'ₕ', 'i' => 'ᵢ', 'j' => 'ⱼ', 'k' => 'ₖ', 'l' => 'ₗ',
+        'm' => 'ₘ', 'n' => 'ₙ', 'o' => 'ₒ', 'p' => 'ₚ', 'r' => 'ᵣ', 's' => 'ₛ', 't' => 'ₜ',
+        'u' => 'ᵤ', 'v' => 'ᵥ', 'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+/// Convert the math to unicode, unknown commands are kept as is
+pub fn to_unicode(latex: &str) -> String {
+    let mut converter = Converter { chars: latex.chars().collect(), position: 0 };
+    converter.convert_until(None)
+}
+
+struct Converter {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Converter {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.position).copied();
+        self.position += 1;
+        c
+    }
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    fn convert_until(&mut self, end: Option<char>) -> String {
+        let mut result = String::new();
+        while let Some(c) = self.peek() {
+            if Some(c) == end {
+                self.position += 1;
+                break;
+            }
+            match c {
+                '^' | '_' => {
+                    self.position += 1;
+                    let argument = self.argument();
+                    let map = if c == '^' { superscript } else { subscript };
+                    match argument.chars().map(map).collect::<Option<String>>() {
+                        Some(script) => result.push_str(&script),
+                        None => {
+                            result.push(c);
+                            result.push_str(&Self::group(&argument));
+                        }
+                    }
+                }
+                _ => result.push_str(&self.argument()),
+            }
+        }
+        result
+    }
+    /// Convert one character, one command or one group between braces
+    fn argument(&mut self) -> String {
+        match self.next() {
+            Some('{') => self.convert_until(Some('}')),
+            Some('\\') => self.command(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        }
+    }
+    fn command(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            name.push(c);
+            self.position += 1;
+        }
+        if name.is_empty() {
+            return match self.next() {
+                Some(',' | ';' | ':' | ' ') => " ".to_string(),
+                Some(c) => c.to_string(),
+                None => "\\".to_string(),
+            };
+        }
+        match name.as_str() {
+            "frac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                Self::fraction(&numerator, &denominator)
+            }
+            "sqrt" => format!("√{}", Self::group(&self.argument())),
+            "left" | "right" => String::new(),
+            name if TEXT_COMMANDS.contains(&name) => self.argument(),
+            name => match SYMBOLS.iter().find(|(n, _)| *n == name) {
+                Some((_, symbol)) => symbol.to_string(),
+                None => format!("\\{}", name),
+            },
+        }
+    }
+    fn fraction(numerator: &str, denominator: &str) -> String {
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+        if is_number(numerator) && is_number(denominator) {
+            let numerator: String = numerator.chars().filter_map(superscript).collect();
+            let denominator: String = denominator.chars().filter_map(subscript).collect();
+            return format!("{}⁄{}", numerator, denominator);
+        }
+        format!("{}/{}", Self::group(numerator), Self::group(denominator))
+    }
+    /// Surround with parentheses the text of several characters
+    fn group(text: &str) -> String {
+        if text.chars().count() > 1 {
+            format!("({})", text)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -1,6 +1,9 @@
-mod token;
+pub mod config;
+pub mod theme;
+pub mod token;
+mod latex;
 mod parser;
-mod renderer;
+pub(crate) mod renderer;
 
 pub use parser::Parser;
-pub use renderer::{Renderer, TerminalRenderer};
\ No newline at end of file
+pub use renderer::{Renderer, TerminalRenderer, HtmlRenderer, PlainRenderer, RecordingRenderer, terminal_width};
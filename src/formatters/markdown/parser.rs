@@ -2,6 +2,7 @@ use thiserror::Error;
 use super::super::Formatter;
 use super::renderer::Renderer;
 use super::token;
+use super::latex;
 use anyhow::Result;
 
 #[derive(Debug, Error)]
@@ -10,13 +11,43 @@ pub enum ParseError<RendererErr> {
     RendererError(#[from] RendererErr),
 }
 
+/// Link being parsed, until the end of its url
+#[derive(Default)]
+struct PendingLink {
+    image: bool,
+    text: String,
+    /// Depth of the brackets in the text
+    depth: usize,
+    text_closed: bool,
+    url: Option<String>,
+}
+
+/// Analysis of the next character in the current mode
+type ModeFunc<R> = fn(&mut Parser<R>, char) -> Result<(), ParseError<<R as Renderer>::Error>>;
+
 pub struct Parser<R: Renderer> {
     renderer: R,
     current_text: String,
     current_token: String,
     previous_char: Option<char>,
     inline_style_tokens: Vec<token::InlineStyleToken>,
-    mode_func: fn(&mut Self, char) -> Result<(), ParseError<R::Error>>,
+    /// Spaces at the beginning of the current line, not yet written
+    indent: usize,
+    /// Indentation of the current list items, by level
+    list_indents: Vec<usize>,
+    /// Whether the previous line was a table row
+    in_table: bool,
+    /// Current table row, parsed once complete
+    table_row: String,
+    /// Whether the last character was a block quote marker
+    after_quote: bool,
+    /// Whether the last character was a backslash, escaping the next one
+    after_backslash: bool,
+    link: Option<PendingLink>,
+    autolink: Option<String>,
+    math: Option<String>,
+    in_code_block: bool,
+    mode_func: ModeFunc<R>,
 }
 
 impl<R: Renderer> Formatter for Parser<R> {
@@ -28,6 +59,17 @@ impl<R: Renderer> Formatter for Parser<R> {
         Ok(self.renderer.flush()?)
     }
     fn end_of_document(&mut self) -> Result<()> {
+        self.abort_pending()?;
+        // the last token is ended by the end of the document
+        if self.in_code_block {
+            self.apply_code_token('\n')?;
+        } else if !self.current_token.is_empty() {
+            self.apply_text_token('\n', false)?;
+        }
+        if !self.table_row.is_empty() {
+            self.push_table_row()?;
+        }
+        self.end_table()?;
         self.push_current_text()?;
         self.renderer.push_token(token::Token::EndDocument)?;
         Ok(())
@@ -43,10 +85,36 @@ impl<R: Renderer> Parser<R> {
             current_token: String::with_capacity(3),
             previous_char: None,
             inline_style_tokens: Vec::new(),
+            indent: 0,
+            list_indents: Vec::new(),
+            in_table: false,
+            table_row: String::new(),
+            after_quote: false,
+            after_backslash: false,
+            link: None,
+            autolink: None,
+            math: None,
+            in_code_block: false,
             mode_func: Self::analyse_text,
         }
     }
+    pub fn into_renderer(self) -> R {
+        self.renderer
+    }
+    pub fn renderer_mut(&mut self) -> &mut R {
+        &mut self.renderer
+    }
     fn analyse_text(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        if self.previous_char.is_none() && c != '|' {
+            self.end_table()?;
+        }
+        let after_quote = std::mem::take(&mut self.after_quote);
+        if self.current_token == "!" && c != '[' {
+            // not an image, the `!` is text and the character is analysed as usual
+            self.current_token.clear();
+            self.apply_text_token('!', true)?;
+            self.previous_char = Some('!');
+        }
         match c {
             '\n' => {
                 self.apply_text_token(c, false)?;
@@ -54,13 +122,64 @@ impl<R: Renderer> Parser<R> {
                 self.renderer.push_token(token::Token::Newline)?;
                 self.previous_char = None;
             }
-            '*' | '_' if self.inline_style_tokens.last() != Some(&token::InlineStyleToken::OneQuote) && (self.current_token.contains(c) || self.current_token.is_empty()) => {
+            ' ' if after_quote => (),
+            ' ' if self.previous_char.is_none() && self.current_token.is_empty() => self.indent += 1,
+            '\t' if self.previous_char.is_none() && self.current_token.is_empty() => self.indent += 4,
+            '*' | '_' | '~' if !self.in_code_span() && (self.current_token.contains(c) || self.current_token.is_empty()) => {
                 self.current_token.push(c);
             }
             '`' if self.current_token.contains(c) || self.current_token.is_empty() => {
                 self.current_token.push(c);
             }
             '-' | '#' if self.previous_char.is_none() => self.current_token.push(c),
+            '|' if self.previous_char.is_none() && self.current_token.is_empty() => {
+                self.end_list();
+                self.in_table = true;
+                self.table_row.push(c);
+                self.mode_func = Self::analyse_table;
+            }
+            '>' if self.previous_char.is_none() && self.current_token.is_empty() => {
+                self.end_list();
+                self.renderer.push_token(token::Token::BlockQuote)?;
+                self.after_quote = true;
+            }
+            '!' if self.current_token.is_empty() => self.current_token.push(c),
+            '\\' | '$' | '[' | '<' => {
+                let image = c == '[' && self.current_token == "!";
+                if image {
+                    self.current_token.clear();
+                }
+                self.apply_text_token(c, false)?;
+                self.previous_char = Some(c);
+                if self.in_code_span() {
+                    if image {
+                        self.current_text.push('!');
+                    }
+                    self.current_text.push(c);
+                    return Ok(());
+                }
+                match c {
+                    '\\' => {
+                        self.after_backslash = true;
+                        self.mode_func = Self::analyse_escape;
+                    }
+                    '$' => {
+                        self.math = Some(String::new());
+                        self.mode_func = Self::analyse_math;
+                    }
+                    '[' => {
+                        self.link = Some(PendingLink { image, ..Default::default() });
+                        self.mode_func = Self::analyse_link;
+                    }
+                    _ => {
+                        self.autolink = Some(String::new());
+                        self.mode_func = Self::analyse_autolink;
+                    }
+                }
+            }
+            '+' if self.previous_char.is_none() && self.current_token.is_empty() => self.current_token.push(c),
+            '0'..='9' if self.previous_char.is_none() && self.current_token.chars().all(|c| c.is_ascii_digit()) => self.current_token.push(c),
+            '.' if self.previous_char.is_none() && !self.current_token.is_empty() && self.current_token.chars().all(|c| c.is_ascii_digit()) => self.current_token.push(c),
             _ => {
                 self.apply_text_token(c, true)?;
                 self.previous_char = Some(c);
@@ -89,6 +208,7 @@ impl<R: Renderer> Parser<R> {
     fn apply_code_token(&mut self, _: char) -> Result<(), ParseError<R::Error>> {
         if self.current_token == "```" {
             self.current_token.clear();
+            self.in_code_block = false;
             self.mode_func = Self::analyse_text;
             self.renderer.push_token(token::Token::EndCode)?;
         }
@@ -96,6 +216,7 @@ impl<R: Renderer> Parser<R> {
         Ok(())
     }
     fn apply_text_token(&mut self, current_char: char, print_current_char: bool) -> Result<(), ParseError<R::Error>> {
+        let line_start = self.previous_char.is_none() && current_char != '\n';
         'skip: {
             if self.current_token.is_empty() {
                 break 'skip;
@@ -103,23 +224,36 @@ impl<R: Renderer> Parser<R> {
 
             if self.previous_char.is_none() && !self.current_token.is_empty() {
                 if self.current_token == "```" {
+                    self.end_list();
                     self.renderer.push_token(token::Token::BeginCode)?;
                     self.current_token.clear();
+                    self.in_code_block = true;
                     self.mode_func = Self::analyse_code_block;
+                    if print_current_char {
+                        // first character of the language
+                        self.current_text.push(current_char);
+                    }
                     return Ok(());
                 } else if self.current_token.len() >= 3 && current_char == '\n' && (self.current_token.chars().all(|c| c == '-') || self.current_token.chars().all(|c| c == '_')) {
+                    self.end_list();
                     self.renderer.push_token(token::Token::Line)?;
                     self.current_token.clear();
                     return Ok(());
                 } else if self.current_token.chars().all(|c| c == '#' ) && current_char == ' ' {
+                    self.end_list();
                     let level = self.current_token.len().into();
                     self.renderer.push_token(token::Token::Heading(level))?;
                     self.current_token.clear();
                     return Ok(());
+                } else if current_char == ' ' {
+                    if let Some(kind) = Self::list_kind(&self.current_token) {
+                        self.push_list_item(kind)?;
+                        return Ok(());
+                    }
                 }
-                self.previous_char = self.current_token.chars().last();
             }
-            let check_char = |c: char| !(c.is_alphanumeric() || ['*', '_', '`'].contains(&c));
+            self.flush_indent(line_start);
+            let check_char = |c: char| !(c.is_alphanumeric() || ['*', '_', '`', '~'].contains(&c));
             let is_begin = matches!(self.previous_char.map(check_char), Some(true) | None);
             let is_end = check_char(current_char); // note: newline MUST resets state, so no need to check
             if is_begin == is_end && !is_begin {
@@ -132,12 +266,28 @@ impl<R: Renderer> Parser<R> {
                 "_" => token::InlineStyleToken::OneDash,
                 "__" => token::InlineStyleToken::TwoDashes,
                 "`" => token::InlineStyleToken::OneQuote,
+                "``" => token::InlineStyleToken::TwoQuotes,
+                "~~" => token::InlineStyleToken::TwoTildes,
                 _ => break 'skip,
             };
+            if self.in_code_span() && self.inline_style_tokens.last() != Some(&inline_style) {
+                // only the delimiter of the code span ends it, e.g. `` a`b ``
+                break 'skip;
+            }
             let inline_style = match self.inline_style_tokens.last() {
                 Some(v) if v == &inline_style => token::Marker::End(inline_style),
                 _ => token::Marker::Begin(inline_style),
             };
+            let is_flanking = match &inline_style {
+                token::Marker::Begin(token::InlineStyleToken::OneQuote | token::InlineStyleToken::TwoQuotes)
+                | token::Marker::End(token::InlineStyleToken::OneQuote | token::InlineStyleToken::TwoQuotes) => true,
+                token::Marker::Begin(_) => !current_char.is_whitespace(),
+                token::Marker::End(_) => !self.previous_char.is_some_and(char::is_whitespace),
+            };
+            if !is_flanking {
+                // e.g. "2 * 3 * 4"
+                break 'skip;
+            }
             match &inline_style {
                 token::Marker::Begin(inline_style) => self.inline_style_tokens.push(inline_style.clone()),
                 token::Marker::End(_) => { self.inline_style_tokens.pop(); },
@@ -146,6 +296,7 @@ impl<R: Renderer> Parser<R> {
             self.renderer.push_token(token::Token::InlineStyle(inline_style))?;
             self.current_token.clear();
         }
+        self.flush_indent(line_start);
         if !self.current_token.is_empty() {
             self.current_text.push_str(&self.current_token);
             self.current_token.clear();
@@ -157,6 +308,302 @@ impl<R: Renderer> Parser<R> {
     }
     
 
+    /// Whether the text is in inline code, where the markdown syntax is ignored
+    fn in_code_span(&self) -> bool {
+        matches!(self.inline_style_tokens.last(), Some(token::InlineStyleToken::OneQuote | token::InlineStyleToken::TwoQuotes))
+    }
+    /// Escaped punctuation characters are written as is: `\*`
+    fn analyse_escape(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        self.mode_func = Self::analyse_text;
+        self.after_backslash = false;
+        if c.is_ascii_punctuation() {
+            self.current_text.push(c);
+            self.previous_char = Some(c);
+            return Ok(());
+        }
+        self.current_text.push('\\');
+        (self.mode_func)(self, c)
+    }
+    /// Inline math is buffered until the closing `$`, then converted to unicode
+    fn analyse_math(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        let Some(math) = self.math.as_mut() else { unreachable!("Math expected in math mode") };
+        match c {
+            '$' if math.is_empty() || math.ends_with(char::is_whitespace) => {
+                // not math: "$$" or "$5 and $"
+                math.push(c);
+                return self.abort_pending();
+            }
+            '$' if !math.ends_with('\\') => {
+                let math = self.math.take().unwrap_or_default();
+                self.mode_func = Self::analyse_text;
+                self.push_current_text()?;
+                self.renderer.push_token(token::Token::InlineStyle(token::Marker::Begin(token::InlineStyleToken::OneDollar)))?;
+                self.renderer.push_token(token::Token::Text(latex::to_unicode(&math)))?;
+                self.renderer.push_token(token::Token::InlineStyle(token::Marker::End(token::InlineStyleToken::OneDollar)))?;
+                self.previous_char = Some(c);
+            }
+            '\n' => return self.abort_pending_with(c),
+            ' ' | '\t' if math.is_empty() => return self.abort_pending_with(c),
+            _ => math.push(c),
+        }
+        Ok(())
+    }
+    fn list_kind(marker: &str) -> Option<token::ListKind> {
+        match marker {
+            "-" | "*" | "+" => Some(token::ListKind::Unordered),
+            _ => marker.strip_suffix('.')
+                .and_then(|n| n.parse().ok())
+                .map(token::ListKind::Ordered),
+        }
+    }
+    fn push_list_item(&mut self, kind: token::ListKind) -> Result<(), ParseError<R::Error>> {
+        while self.list_indents.last().is_some_and(|&indent| indent > self.indent) {
+            self.list_indents.pop();
+        }
+        if self.list_indents.last() != Some(&self.indent) {
+            self.list_indents.push(self.indent);
+        }
+        self.indent = 0;
+        self.current_token.clear();
+        self.previous_char = Some(' ');
+        self.renderer.push_token(token::Token::ListItem(token::ListItem {
+            level: (self.list_indents.len() - 1).into(),
+            kind,
+        }))?;
+        self.mode_func = Self::analyse_task;
+        Ok(())
+    }
+    /// Check if the list item begins with a task checkbox (`[ ]` or `[x]`)
+    fn analyse_task(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        const CHECKBOXES: [&str; 3] = ["[ ] ", "[x] ", "[X] "];
+        self.current_token.push(c);
+        if CHECKBOXES.contains(&self.current_token.as_str()) {
+            self.renderer.push_token(token::Token::Checkbox(self.current_token != "[ ] "))?;
+            self.current_token.clear();
+            self.mode_func = Self::analyse_text;
+        } else if !CHECKBOXES.iter().any(|checkbox| checkbox.starts_with(&self.current_token)) {
+            let pending = std::mem::take(&mut self.current_token);
+            self.mode_func = Self::analyse_text;
+            for c in pending.chars() {
+                (self.mode_func)(self, c)?;
+            }
+        }
+        Ok(())
+    }
+    fn end_list(&mut self) {
+        self.indent = 0;
+        self.list_indents.clear();
+    }
+    /// Write the indentation of the line as text
+    fn flush_indent(&mut self, line_start: bool) {
+        if line_start && self.indent == 0 {
+            // a paragraph ends the list
+            self.list_indents.clear();
+        }
+        if self.indent > 0 {
+            self.current_text.extend(std::iter::repeat_n(' ', self.indent));
+            self.indent = 0;
+        }
+    }
+
+    /// Table rows are buffered, then their cells are parsed as text
+    fn analyse_table(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        if c != '\n' {
+            self.table_row.push(c);
+            return Ok(());
+        }
+        self.push_table_row()?;
+        self.previous_char = None;
+        self.mode_func = Self::analyse_text;
+        Ok(())
+    }
+    fn push_table_row(&mut self) -> Result<(), ParseError<R::Error>> {
+        let row = std::mem::take(&mut self.table_row);
+        let cells = Self::split_table_row(&row);
+        if !cells.is_empty() {
+            if let Some(alignments) = cells.iter().map(|cell| Self::cell_alignment(cell)).collect() {
+                self.renderer.push_token(token::Token::TableAlignment(alignments))?;
+                return Ok(());
+            }
+        }
+        self.renderer.push_token(token::Token::TableRow)?;
+        for cell in cells {
+            self.renderer.push_token(token::Token::TableCell)?;
+            self.previous_char = Some(' ');
+            self.mode_func = Self::analyse_text;
+            for c in cell.trim().chars() {
+                (self.mode_func)(self, c)?;
+            }
+            self.abort_pending()?;
+            self.apply_text_token(' ', false)?;
+            self.push_current_text()?;
+            self.inline_style_tokens.clear();
+        }
+        Ok(())
+    }
+    /// Split a table row on the pipes which are neither escaped nor in inline code
+    fn split_table_row(row: &str) -> Vec<String> {
+        let mut cells = vec![String::new()];
+        let mut in_code = false;
+        let mut chars = row.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'|') => cells.last_mut().unwrap().push(chars.next().unwrap()),
+                '|' if !in_code => cells.push(String::new()),
+                '`' => {
+                    in_code = !in_code;
+                    cells.last_mut().unwrap().push(c);
+                }
+                _ => cells.last_mut().unwrap().push(c),
+            }
+        }
+        // the row begins with a pipe
+        cells.remove(0);
+        if cells.last().is_some_and(|cell| cell.trim().is_empty()) {
+            cells.pop();
+        }
+        cells
+    }
+    /// Alignment of the cell if the cell is part of the delimiter row (`| :--- | :---: |`)
+    fn cell_alignment(cell: &str) -> Option<token::Alignment> {
+        let cell = cell.trim();
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+            return None;
+        }
+        Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => token::Alignment::Center,
+            (true, false) => token::Alignment::Left,
+            (false, true) => token::Alignment::Right,
+            (false, false) => token::Alignment::Default,
+        })
+    }
+    fn end_table(&mut self) -> Result<(), ParseError<R::Error>> {
+        if self.in_table {
+            self.in_table = false;
+            self.renderer.push_token(token::Token::EndTable)?;
+        }
+        Ok(())
+    }
+
+    /// Links are buffered until the end of the url: `[text](url)` or `![alt](url)`
+    fn analyse_link(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        let Some(link) = self.link.as_mut() else { unreachable!("Link expected in link mode") };
+        match (&mut link.url, c) {
+            (_, '\n') => return self.abort_pending_with(c),
+            (None, ']') if !link.text_closed && link.depth == 0 => link.text_closed = true,
+            (None, '(') if link.text_closed => link.url = Some(String::new()),
+            (None, _) if link.text_closed => return self.abort_pending_with(c),
+            (None, _) => {
+                match c {
+                    '[' => link.depth += 1,
+                    ']' => link.depth -= 1,
+                    _ => (),
+                }
+                link.text.push(c);
+            }
+            (Some(_), ')') => return self.push_link(),
+            (Some(url), _) => url.push(c),
+        }
+        Ok(())
+    }
+    fn push_link(&mut self) -> Result<(), ParseError<R::Error>> {
+        let Some(link) = self.link.take() else { return Ok(()) };
+        self.mode_func = Self::analyse_text;
+        // the title of the link is ignored: [text](url "title")
+        let url = link.url.unwrap_or_default().split_whitespace().next().unwrap_or_default().to_string();
+        self.push_current_text()?;
+        if link.image {
+            self.renderer.push_token(token::Token::Image { alt: link.text, url })?;
+        } else {
+            self.renderer.push_token(token::Token::BeginLink(url))?;
+            for c in link.text.chars() {
+                (self.mode_func)(self, c)?;
+            }
+            self.abort_pending()?;
+            self.apply_text_token(')', false)?;
+            self.push_current_text()?;
+            self.renderer.push_token(token::Token::EndLink)?;
+        }
+        self.previous_char = Some(')');
+        Ok(())
+    }
+    /// Autolinks are buffered until the closing `>`: `<https://example.com>`
+    fn analyse_autolink(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        static SCHEME_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+            regex::Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]{1,31}:").expect("Failed to compile regex")
+        });
+        let Some(autolink) = self.autolink.as_mut() else { unreachable!("Autolink expected in autolink mode") };
+        match c {
+            '>' => {
+                let url = if SCHEME_RE.is_match(autolink) {
+                    autolink.clone()
+                } else if autolink.contains('@') && !autolink.starts_with('@') {
+                    format!("mailto:{}", autolink)
+                } else {
+                    autolink.push(c);
+                    return self.abort_pending();
+                };
+                let text = self.autolink.take().unwrap_or_default();
+                self.mode_func = Self::analyse_text;
+                self.push_current_text()?;
+                self.renderer.push_token(token::Token::BeginLink(url))?;
+                self.renderer.push_token(token::Token::Text(text))?;
+                self.renderer.push_token(token::Token::EndLink)?;
+                self.previous_char = Some(c);
+            }
+            ' ' | '\t' | '\n' | '<' => return self.abort_pending_with(c),
+            _ => autolink.push(c),
+        }
+        Ok(())
+    }
+    fn abort_pending_with(&mut self, c: char) -> Result<(), ParseError<R::Error>> {
+        self.abort_pending()?;
+        (self.mode_func)(self, c)
+    }
+    /// Parse again as text the link or the math being parsed, if any
+    fn abort_pending(&mut self) -> Result<(), ParseError<R::Error>> {
+        let pending = if let Some(link) = self.link.take() {
+            let mut pending = String::from(if link.image { "![" } else { "[" });
+            pending.push_str(&link.text);
+            if link.text_closed {
+                pending.push(']');
+            }
+            if let Some(url) = link.url {
+                pending.push('(');
+                pending.push_str(&url);
+            }
+            pending
+        } else if let Some(autolink) = self.autolink.take() {
+            format!("<{}", autolink)
+        } else if let Some(math) = self.math.take() {
+            format!("${}", math)
+        } else {
+            return self.abort_escape();
+        };
+        self.mode_func = Self::analyse_text;
+        let mut chars = pending.chars();
+        // the opening character is written as text, not to parse the link again
+        for c in chars.by_ref() {
+            self.current_text.push(c);
+            if c != '!' {
+                break;
+            }
+        }
+        for c in chars {
+            (self.mode_func)(self, c)?;
+        }
+        self.abort_escape()
+    }
+    /// Write as is the backslash which has no character left to escape
+    fn abort_escape(&mut self) -> Result<(), ParseError<R::Error>> {
+        if std::mem::take(&mut self.after_backslash) {
+            self.current_text.push('\\');
+            self.mode_func = Self::analyse_text;
+        }
+        Ok(())
+    }
+
     fn push_current_text(&mut self) -> Result<(), ParseError<R::Error>> {
         if !self.current_text.is_empty() {
             let mut s = String::new();
@@ -0,0 +1,117 @@
+use once_cell::sync::Lazy;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style, Theme, ThemeSet},
+    html::{self, ClassStyle},
+    parsing::{ParseState, ScopeStack, SyntaxSet},
+};
+use crossterm::{queue, style::*};
+use std::io::{Error, Write};
+
+static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+/// Classes of the highlighted html, prefixed to not collide with the styles of the page
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "syntax-" };
+
+fn find_theme(theme: &str) -> Option<&'static Theme> {
+    match THEME_SET.themes.get(theme) {
+        Some(theme) => Some(theme),
+        None => {
+            log::warn!("Unknown syntax theme `{}`, fallback to `{}`", theme, DEFAULT_THEME);
+            THEME_SET.themes.get(DEFAULT_THEME)
+        }
+    }
+}
+
+/// Line by line syntax highlighter of a code block
+pub struct Highlighter {
+    lines: HighlightLines<'static>,
+    true_color: bool,
+}
+
+impl Highlighter {
+    /// Create a highlighter for the language, if the language is known
+    pub fn new(language: &str, theme: &str) -> Option<Self> {
+        let syntax = SYNTAX_SET.find_syntax_by_token(language.trim())?;
+        let theme = find_theme(theme)?;
+        let true_color = std::env::var("COLORTERM").map(|v| v == "truecolor" || v == "24bit").unwrap_or(false);
+        Some(Self {
+            lines: HighlightLines::new(syntax, theme),
+            true_color,
+        })
+    }
+    /// Highlight the next line of the code block
+    ///
+    /// Each line must be highlighted in order, even the ones not drawn, to keep the state of the parser.
+    pub fn highlight<'a>(&mut self, line: &'a str) -> Result<Vec<(Style, &'a str)>, Error> {
+        self.lines
+            .highlight_line(line, &SYNTAX_SET)
+            .map_err(Error::other)
+    }
+    pub fn draw(&self, out: &mut impl Write, ranges: &[(Style, &str)]) -> Result<(), Error> {
+        for (style, text) in ranges {
+            let text = text.trim_end_matches('\n');
+            if text.is_empty() {
+                continue;
+            }
+            let mut content = text.with(self.color(style.foreground));
+            if style.font_style.contains(FontStyle::BOLD) {
+                content = content.attribute(Attribute::Bold);
+            }
+            if style.font_style.contains(FontStyle::ITALIC) {
+                content = content.attribute(Attribute::Italic);
+            }
+            if style.font_style.contains(FontStyle::UNDERLINE) {
+                content = content.attribute(Attribute::Underlined);
+            }
+            queue!(out, PrintStyledContent(content))?;
+        }
+        queue!(out, ResetColor, SetAttribute(Attribute::Reset))
+    }
+    fn color(&self, color: syntect::highlighting::Color) -> Color {
+        if self.true_color {
+            return Color::Rgb { r: color.r, g: color.g, b: color.b };
+        }
+        // nearest color of the 6x6x6 cube of the 256 colors palette
+        let cube = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+        Color::AnsiValue(16 + 36 * cube(color.r) + 6 * cube(color.g) + cube(color.b))
+    }
+}
+
+/// Line by line syntax highlighter of a code block, writing html spans with classes
+pub struct HtmlHighlighter {
+    parse_state: ParseState,
+    scope_stack: ScopeStack,
+    /// Spans still open at the end of the last line
+    open_spans: isize,
+}
+
+impl HtmlHighlighter {
+    /// Create a highlighter for the language, if the language is known
+    pub fn new(language: &str) -> Option<Self> {
+        let syntax = SYNTAX_SET.find_syntax_by_token(language.trim())?;
+        Some(Self {
+            parse_state: ParseState::new(syntax),
+            scope_stack: ScopeStack::new(),
+            open_spans: 0,
+        })
+    }
+    /// Highlighted and escaped html of the next line, which must end with a newline
+    pub fn highlight(&mut self, line: &str) -> Result<String, Error> {
+        let ops = self.parse_state.parse_line(line, &SYNTAX_SET).map_err(Error::other)?;
+        let (html, delta) = html::line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut self.scope_stack)
+            .map_err(Error::other)?;
+        self.open_spans += delta;
+        Ok(html)
+    }
+    /// Close the spans left open by the last lines
+    pub fn finish(self) -> String {
+        "</span>".repeat(self.open_spans.max(0) as usize)
+    }
+    /// Style sheet of the classes for the theme, applied to the elements of class `syntax-code`
+    pub fn css(theme: &str) -> Result<String, Error> {
+        let theme = find_theme(theme).ok_or_else(|| Error::other("No syntax theme"))?;
+        html::css_for_theme_with_class_style(theme, CLASS_STYLE).map_err(Error::other)
+    }
+}
@@ -0,0 +1,454 @@
+use super::highlight::HtmlHighlighter;
+use super::token::{self, InlineStyleToken, Token};
+use super::Renderer;
+use super::super::config::Config;
+use std::io::{Error, Write};
+
+const PAGE_STYLE: &str = "\
+body { max-width: 50em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.5; }
+pre { padding: 0.5em 1em; overflow-x: auto; }
+code { font-family: monospace; }
+blockquote { margin-left: 0; padding-left: 1em; border-left: 0.25em solid #ccc; color: #555; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; }
+.math { font-style: italic; }";
+
+/// Escape the characters having a meaning in html
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether the url can be written in a link or an image: `http`, `https`, `mailto` or relative
+///
+/// The other schemes, like `javascript:`, would run in the browser opening the page.
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim();
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => matches!(url[..end].to_ascii_lowercase().as_str(), "http" | "https" | "mailto"),
+        _ => true,
+    }
+}
+
+fn opening_tag(style: &InlineStyleToken) -> &'static str {
+    match style {
+        InlineStyleToken::OneStar | InlineStyleToken::OneDash => "<em>",
+        InlineStyleToken::TwoStars | InlineStyleToken::TwoDashes => "<strong>",
+        InlineStyleToken::ThreeStars => "<strong><em>",
+        InlineStyleToken::OneQuote | InlineStyleToken::TwoQuotes => "<code>",
+        InlineStyleToken::TwoTildes => "<del>",
+        InlineStyleToken::OneDollar => "<span class=\"math\">",
+        InlineStyleToken::Link => "<a>",
+    }
+}
+
+fn closing_tag(style: &InlineStyleToken) -> &'static str {
+    match style {
+        InlineStyleToken::OneStar | InlineStyleToken::OneDash => "</em>",
+        InlineStyleToken::TwoStars | InlineStyleToken::TwoDashes => "</strong>",
+        InlineStyleToken::ThreeStars => "</em></strong>",
+        InlineStyleToken::OneQuote | InlineStyleToken::TwoQuotes => "</code>",
+        InlineStyleToken::TwoTildes => "</del>",
+        InlineStyleToken::OneDollar => "</span>",
+        InlineStyleToken::Link => "</a>",
+    }
+}
+
+/// Element containing the text of the current line
+#[derive(PartialEq, Default)]
+enum Block {
+    #[default]
+    None,
+    Paragraph,
+    Heading(usize),
+    ListItem,
+}
+
+#[derive(Default)]
+struct CodeBlock {
+    language: String,
+    /// Whether the line of the language is ended and the code is started
+    started: bool,
+    line: String,
+    highlighter: Option<HtmlHighlighter>,
+}
+
+/// Table buffered until its end, the header is known once the alignment row is parsed
+#[derive(Default)]
+struct Table {
+    alignments: Vec<token::Alignment>,
+    header_rows: usize,
+    /// Html of the cells
+    rows: Vec<Vec<String>>,
+}
+
+/// Renderer writing a standalone html page
+pub struct HtmlRenderer<W: Write = std::io::Stdout> {
+    out: W,
+    /// Theme of the code blocks, or None if the syntax highlighting is disabled
+    syntax_theme: Option<String>,
+    /// Whether the beginning of the page is written
+    started: bool,
+    line_start: bool,
+    block: Block,
+    /// Tags of the open lists, from the outermost
+    lists: Vec<&'static str>,
+    /// Number of open block quotes
+    quotes: usize,
+    /// Number of block quotes of the current line
+    line_quotes: usize,
+    inline_styles: Vec<InlineStyleToken>,
+    code: Option<CodeBlock>,
+    table: Option<Table>,
+    /// Url of the current link if it is not safe, written as text after the link text
+    unsafe_link: Option<String>,
+}
+
+impl HtmlRenderer {
+    pub fn new(config: &Config) -> Self {
+        Self::with_output(std::io::stdout(), config)
+    }
+}
+
+impl<W: Write> HtmlRenderer<W> {
+    pub fn with_output(out: W, config: &Config) -> Self {
+        Self {
+            out,
+            syntax_theme: config.syntax_highlighting.then(|| config.syntax_theme.clone()),
+            started: false,
+            line_start: true,
+            block: Block::None,
+            lists: Vec::new(),
+            quotes: 0,
+            line_quotes: 0,
+            inline_styles: Vec::new(),
+            code: None,
+            table: None,
+            unsafe_link: None,
+        }
+    }
+    pub fn into_output(self) -> W {
+        self.out
+    }
+    /// Write the html, in the current cell if a table is parsed
+    fn write(&mut self, html: &str) -> Result<(), Error> {
+        match self.table.as_mut().and_then(|table| table.rows.last_mut()).and_then(|row| row.last_mut()) {
+            Some(cell) => {
+                cell.push_str(html);
+                Ok(())
+            }
+            None => self.out.write_all(html.as_bytes()),
+        }
+    }
+    fn write_page_start(&mut self) -> Result<(), Error> {
+        let syntax_style = match &self.syntax_theme {
+            Some(theme) => HtmlHighlighter::css(theme)?,
+            None => String::new(),
+        };
+        write!(self.out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n{}\n{}</style>\n</head>\n<body>\n",
+            PAGE_STYLE, syntax_style
+        )
+    }
+    /// Open the elements of the line, depending on its first token
+    fn begin_line(&mut self, token: &Token) -> Result<(), Error> {
+        self.line_start = false;
+        let quotes = std::mem::take(&mut self.line_quotes);
+        self.set_quotes(quotes)?;
+        match token {
+            Token::ListItem(_) => self.close_paragraph(),
+            Token::Heading(_) | Token::Line | Token::BeginCode | Token::TableRow => {
+                self.close_paragraph()?;
+                self.close_lists()
+            }
+            _ => {
+                self.close_lists()?;
+                if self.block == Block::Paragraph {
+                    // the lines of a paragraph are joined
+                    self.write("\n")
+                } else {
+                    self.block = Block::Paragraph;
+                    self.write("<p>")
+                }
+            }
+        }
+    }
+    /// Open or close the block quotes, ending the blocks of the previous level
+    fn set_quotes(&mut self, quotes: usize) -> Result<(), Error> {
+        if quotes == self.quotes {
+            return Ok(());
+        }
+        self.close_paragraph()?;
+        self.close_lists()?;
+        while self.quotes > quotes {
+            self.quotes -= 1;
+            self.write("</blockquote>\n")?;
+        }
+        while self.quotes < quotes {
+            self.quotes += 1;
+            self.write("<blockquote>\n")?;
+        }
+        Ok(())
+    }
+    fn close_paragraph(&mut self) -> Result<(), Error> {
+        if self.block == Block::Paragraph {
+            self.block = Block::None;
+            self.write("</p>\n")?;
+        }
+        Ok(())
+    }
+    fn close_lists(&mut self) -> Result<(), Error> {
+        if self.block == Block::ListItem {
+            self.block = Block::None;
+        }
+        while let Some(tag) = self.lists.pop() {
+            self.write(&format!("</li>\n</{}>\n", tag))?;
+        }
+        Ok(())
+    }
+    fn push_list_item(&mut self, item: token::ListItem) -> Result<(), Error> {
+        // a list is nested in the last item of its parent
+        let level = usize::from(item.level).min(self.lists.len());
+        let tag = match item.kind {
+            token::ListKind::Unordered => "ul",
+            token::ListKind::Ordered(_) => "ol",
+        };
+        while self.lists.len() > level + 1 || (self.lists.len() == level + 1 && self.lists.last() != Some(&tag)) {
+            let tag = self.lists.pop().unwrap_or_default();
+            self.write(&format!("</li>\n</{}>\n", tag))?;
+        }
+        if self.lists.len() == level + 1 {
+            self.write("</li>\n")?;
+        } else {
+            match item.kind {
+                token::ListKind::Ordered(start) if start != 1 => self.write(&format!("<ol start=\"{}\">\n", start))?,
+                _ => self.write(&format!("<{}>\n", tag))?,
+            }
+            self.lists.push(tag);
+        }
+        self.block = Block::ListItem;
+        self.write("<li>")
+    }
+    fn close_style(&mut self, style: &InlineStyleToken) -> Result<(), Error> {
+        if !self.inline_styles.contains(style) {
+            return Ok(());
+        }
+        while let Some(open_style) = self.inline_styles.pop() {
+            self.write(closing_tag(&open_style))?;
+            if &open_style == style {
+                break;
+            }
+        }
+        Ok(())
+    }
+    fn close_inline_styles(&mut self) -> Result<(), Error> {
+        while let Some(style) = self.inline_styles.pop() {
+            self.write(closing_tag(&style))?;
+        }
+        Ok(())
+    }
+    fn push_code_token(&mut self, token: Token) -> Result<(), Error> {
+        let Some(code) = self.code.as_mut() else { return Ok(()) };
+        match token {
+            Token::Text(s) if !code.started => code.language.push_str(&s),
+            Token::Text(s) => code.line.push_str(&s),
+            Token::Newline if !code.started => self.start_code()?,
+            Token::Newline => {
+                code.line.push('\n');
+                let line = std::mem::take(&mut code.line);
+                let html = match code.highlighter.as_mut() {
+                    Some(highlighter) => highlighter.highlight(&line)?,
+                    None => escape(&line),
+                };
+                self.write(&html)?;
+            }
+            Token::EndCode => self.end_code()?,
+            _ => unreachable!("Token not supported in code mode: {:?}", token),
+        }
+        Ok(())
+    }
+    fn start_code(&mut self) -> Result<(), Error> {
+        let Some(code) = self.code.as_mut() else { return Ok(()) };
+        code.started = true;
+        let language = code.language.trim().to_string();
+        if self.syntax_theme.is_some() {
+            code.highlighter = HtmlHighlighter::new(&language);
+        }
+        match language.as_str() {
+            "" => self.write("<pre class=\"syntax-code\"><code>"),
+            language => self.write(&format!("<pre class=\"syntax-code\"><code class=\"language-{}\">", escape(language))),
+        }
+    }
+    fn end_code(&mut self) -> Result<(), Error> {
+        if !self.code.as_ref().is_some_and(|code| code.started) {
+            self.start_code()?;
+        }
+        let Some(code) = self.code.take() else { return Ok(()) };
+        let mut html = escape(&code.line);
+        if let Some(highlighter) = code.highlighter {
+            html.push_str(&highlighter.finish());
+        }
+        self.write(&html)?;
+        self.write("</code></pre>\n")
+    }
+    fn push_table_token(&mut self, token: Token) -> Result<(), Error> {
+        match token {
+            Token::TableRow | Token::TableCell => self.close_inline_styles()?,
+            _ => (),
+        }
+        let Some(table) = self.table.as_mut() else { return Ok(()) };
+        match token {
+            Token::TableRow => table.rows.push(Vec::new()),
+            Token::TableCell => {
+                if let Some(row) = table.rows.last_mut() {
+                    row.push(String::new());
+                }
+            }
+            Token::TableAlignment(alignments) => {
+                table.alignments = alignments;
+                table.header_rows = table.rows.len();
+            }
+            Token::EndTable => self.end_table()?,
+            _ => unreachable!("Token not supported in table mode: {:?}", token),
+        }
+        Ok(())
+    }
+    fn end_table(&mut self) -> Result<(), Error> {
+        self.close_inline_styles()?;
+        let Some(table) = self.table.take() else { return Ok(()) };
+        let mut html = String::from("<table>\n");
+        for (index, row) in table.rows.iter().enumerate() {
+            if index == 0 && table.header_rows > 0 {
+                html.push_str("<thead>\n");
+            }
+            if index == table.header_rows {
+                html.push_str(if index > 0 { "</thead>\n<tbody>\n" } else { "<tbody>\n" });
+            }
+            let tag = if index < table.header_rows { "th" } else { "td" };
+            html.push_str("<tr>");
+            for (column, cell) in row.iter().enumerate() {
+                let alignment = match table.alignments.get(column) {
+                    Some(token::Alignment::Left) => " style=\"text-align: left\"",
+                    Some(token::Alignment::Center) => " style=\"text-align: center\"",
+                    Some(token::Alignment::Right) => " style=\"text-align: right\"",
+                    _ => "",
+                };
+                html.push_str(&format!("<{}{}>{}</{}>", tag, alignment, cell, tag));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str(if table.rows.len() > table.header_rows { "</tbody>\n" } else { "</thead>\n" });
+        html.push_str("</table>\n");
+        self.write(&html)
+    }
+    fn end_document(&mut self) -> Result<(), Error> {
+        if self.code.is_some() {
+            // the document ends inside a code block
+            self.end_code()?;
+        }
+        if self.table.is_some() {
+            self.end_table()?;
+        }
+        self.close_inline_styles()?;
+        if let Block::Heading(level) = self.block {
+            self.write(&format!("</h{}>\n", level))?;
+        }
+        self.close_paragraph()?;
+        self.close_lists()?;
+        self.set_quotes(0)?;
+        self.write("</body>\n</html>\n")
+    }
+}
+
+impl<W: Write> Renderer for HtmlRenderer<W> {
+    type Error = Error;
+    fn push_token(&mut self, token: Token) -> Result<(), Self::Error> {
+        if !self.started {
+            self.started = true;
+            self.write_page_start()?;
+        }
+        if self.code.is_some() && token != Token::EndDocument {
+            return self.push_code_token(token);
+        }
+        if self.line_start {
+            match token {
+                Token::BlockQuote => {
+                    self.line_quotes += 1;
+                    return Ok(());
+                }
+                Token::Newline | Token::EndDocument => (),
+                ref token => self.begin_line(token)?,
+            }
+        }
+        match token {
+            Token::Text(s) => self.write(&escape(&s))?,
+            Token::Newline if self.line_start => {
+                // an empty line ends the blocks
+                let quotes = std::mem::take(&mut self.line_quotes);
+                self.close_paragraph()?;
+                self.close_lists()?;
+                self.set_quotes(quotes)?;
+            }
+            Token::Newline => {
+                self.close_inline_styles()?;
+                if let Block::Heading(level) = self.block {
+                    self.block = Block::None;
+                    self.write(&format!("</h{}>\n", level))?;
+                }
+                self.line_start = true;
+            }
+            Token::InlineStyle(token::Marker::Begin(style)) => {
+                self.write(opening_tag(&style))?;
+                self.inline_styles.push(style);
+            }
+            Token::InlineStyle(token::Marker::End(style)) => self.close_style(&style)?,
+            Token::BeginCode => self.code = Some(CodeBlock::default()),
+            Token::EndCode => unreachable!("Code ending expected only in code mode"),
+            Token::Line => self.write("<hr>\n")?,
+            Token::Heading(level) => {
+                let level = usize::from(level).clamp(1, 6);
+                self.block = Block::Heading(level);
+                self.write(&format!("<h{}>", level))?;
+            }
+            Token::ListItem(item) => self.push_list_item(item)?,
+            Token::Checkbox(checked) => {
+                self.write(if checked { "<input type=\"checkbox\" checked disabled> " } else { "<input type=\"checkbox\" disabled> " })?;
+            }
+            Token::TableRow if self.table.is_none() => {
+                self.table = Some(Table::default());
+                self.push_table_token(Token::TableRow)?;
+            }
+            token @ (Token::TableRow | Token::TableCell | Token::TableAlignment(_)) => self.push_table_token(token)?,
+            Token::EndTable => {
+                self.push_table_token(Token::EndTable)?;
+                self.line_start = true;
+            }
+            Token::BlockQuote => self.write("&gt; ")?,
+            Token::BeginLink(url) if !is_safe_url(&url) => self.unsafe_link = Some(url),
+            Token::BeginLink(url) => {
+                self.write(&format!("<a href=\"{}\">", escape(&url)))?;
+                self.inline_styles.push(InlineStyleToken::Link);
+            }
+            Token::EndLink => match self.unsafe_link.take() {
+                Some(url) => self.write(&escape(&format!(" ({})", url)))?,
+                None => self.close_style(&InlineStyleToken::Link)?,
+            },
+            Token::Image { alt, url } if !is_safe_url(&url) => self.write(&escape(&format!("{} ({})", alt, url)))?,
+            Token::Image { alt, url } => self.write(&format!("<img src=\"{}\" alt=\"{}\">", escape(&url), escape(&alt)))?,
+            Token::EndDocument => self.end_document()?,
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.out.flush()
+    }
+}
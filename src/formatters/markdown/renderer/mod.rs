@@ -1,7 +1,14 @@
-mod terminal;
+pub(crate) mod terminal;
+mod html;
+mod plain;
+mod recording;
+pub(crate) mod highlight;
 
 use super::token;
-pub use terminal::TerminalRenderer;
+pub use terminal::{TerminalRenderer, terminal_width};
+pub use html::HtmlRenderer;
+pub use plain::PlainRenderer;
+pub use recording::RecordingRenderer;
 
 pub trait Renderer {
     type Error: 'static + std::fmt::Debug + std::error::Error + Send + Sync;
@@ -0,0 +1,194 @@
+use super::token::{self, Token};
+use super::Renderer;
+use std::io::{Error, Write};
+use unicode_width::UnicodeWidthStr;
+
+/// Indentation of the code blocks
+const CODE_INDENT: &str = "    ";
+const LIST_INDENT: &str = "  ";
+const LINE_WIDTH: usize = 40;
+
+#[derive(Default)]
+struct Table {
+    alignments: Vec<token::Alignment>,
+    header_rows: usize,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Columns padded to the same width, separated by two spaces
+    fn to_text(&self) -> String {
+        let column_count = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; column_count];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.width());
+            }
+        }
+        let mut text = String::new();
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 && index == self.header_rows {
+                let separator = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  ");
+                text.push_str(&separator);
+                text.push('\n');
+            }
+            let mut line = String::new();
+            for (column, width) in widths.iter().enumerate() {
+                let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                let padding = width - cell.width();
+                let (before, after) = match self.alignments.get(column) {
+                    Some(token::Alignment::Right) => (padding, 0),
+                    Some(token::Alignment::Center) => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+                if column > 0 {
+                    line.push_str("  ");
+                }
+                line.push_str(&" ".repeat(before));
+                line.push_str(cell);
+                line.push_str(&" ".repeat(after));
+            }
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Renderer writing the text without the markdown syntax nor escape codes
+pub struct PlainRenderer<W: Write = std::io::Stdout> {
+    out: W,
+    /// Whether nothing is written on the current line
+    line_start: bool,
+    /// Url and text of the current link
+    link: Option<(String, String)>,
+    /// Whether the code of the current block is started, after the line of the language
+    code: Option<bool>,
+    /// Whether the newline ends a fence of a code block, which is not written
+    in_fence: bool,
+    table: Option<Table>,
+}
+
+impl Default for PlainRenderer {
+    fn default() -> Self {
+        Self::with_output(std::io::stdout())
+    }
+}
+
+impl<W: Write> PlainRenderer<W> {
+    pub fn with_output(out: W) -> Self {
+        Self {
+            out,
+            line_start: true,
+            link: None,
+            code: None,
+            in_fence: false,
+            table: None,
+        }
+    }
+    pub fn into_output(self) -> W {
+        self.out
+    }
+    /// Write the text, in the current cell if a table is parsed
+    fn write(&mut self, text: &str) -> Result<(), Error> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if let Some((_, link_text)) = self.link.as_mut() {
+            link_text.push_str(text);
+        }
+        if let Some(cell) = self.table.as_mut().and_then(|table| table.rows.last_mut()).and_then(|row| row.last_mut()) {
+            cell.push_str(text);
+            return Ok(());
+        }
+        self.line_start = text.ends_with('\n');
+        self.out.write_all(text.as_bytes())
+    }
+    fn end_link(&mut self) -> Result<(), Error> {
+        let Some((url, text)) = self.link.take() else { return Ok(()) };
+        // the url is not repeated for the autolinks
+        if text != url && url.strip_prefix("mailto:") != Some(text.as_str()) {
+            self.write(&format!(" ({})", url))?;
+        }
+        Ok(())
+    }
+    fn push_table_token(&mut self, token: Token) -> Result<(), Error> {
+        let table = self.table.get_or_insert_with(Table::default);
+        match token {
+            Token::TableRow => table.rows.push(Vec::new()),
+            Token::TableCell => {
+                if let Some(row) = table.rows.last_mut() {
+                    row.push(String::new());
+                }
+            }
+            Token::TableAlignment(alignments) => {
+                table.alignments = alignments;
+                table.header_rows = table.rows.len();
+            }
+            Token::EndTable => {
+                let text = table.to_text();
+                self.table = None;
+                self.write(&text)?;
+            }
+            _ => unreachable!("Token not supported in table mode: {:?}", token),
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Renderer for PlainRenderer<W> {
+    type Error = Error;
+    fn push_token(&mut self, token: Token) -> Result<(), Self::Error> {
+        match token {
+            Token::Text(s) => match self.code {
+                Some(false) => (),
+                Some(true) if self.line_start && !s.is_empty() => self.write(&format!("{}{}", CODE_INDENT, s))?,
+                _ => self.write(&s)?,
+            },
+            Token::Newline if self.code == Some(false) => self.code = Some(true),
+            Token::Newline if self.in_fence => self.in_fence = false,
+            Token::Newline => {
+                self.write("\n")?;
+                self.line_start = true;
+            }
+            Token::InlineStyle(_) => (),
+            Token::BeginCode => self.code = Some(false),
+            Token::EndCode => {
+                self.code = None;
+                self.in_fence = true;
+            }
+            Token::Line => self.write(&"-".repeat(LINE_WIDTH))?,
+            Token::Heading(_) => (),
+            Token::ListItem(item) => {
+                let marker = match item.kind {
+                    token::ListKind::Unordered => "-".to_string(),
+                    token::ListKind::Ordered(n) => format!("{}.", n),
+                };
+                self.write(&format!("{}{} ", LIST_INDENT.repeat(item.level.into()), marker))?;
+            }
+            Token::Checkbox(checked) => self.write(if checked { "[x] " } else { "[ ] " })?,
+            token @ (Token::TableRow | Token::TableCell | Token::TableAlignment(_) | Token::EndTable) => self.push_table_token(token)?,
+            Token::BlockQuote => self.write("> ")?,
+            Token::BeginLink(url) => {
+                self.end_link()?;
+                self.link = Some((url, String::new()));
+            }
+            Token::EndLink => self.end_link()?,
+            Token::Image { alt, url } if alt.is_empty() => self.write(&url)?,
+            Token::Image { alt, url } => self.write(&format!("{} ({})", alt, url))?,
+            Token::EndDocument => {
+                self.end_link()?;
+                if self.table.is_some() {
+                    self.push_table_token(Token::EndTable)?;
+                }
+                if !self.line_start {
+                    self.write("\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,33 @@
+use super::token::Token;
+use super::Renderer;
+
+/// Renderer keeping the tokens, to check the output of the parser
+#[derive(Default, Debug)]
+pub struct RecordingRenderer {
+    tokens: Vec<Token>,
+}
+
+impl RecordingRenderer {
+    /// Tokens with the consecutive texts merged, since the texts are split where the chunks end
+    pub fn into_merged_tokens(self) -> Vec<Token> {
+        let mut tokens = Vec::<Token>::with_capacity(self.tokens.len());
+        for token in self.tokens {
+            match (tokens.last_mut(), token) {
+                (Some(Token::Text(last)), Token::Text(text)) => last.push_str(&text),
+                (_, token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    type Error = std::convert::Infallible;
+    fn push_token(&mut self, token: Token) -> Result<(), Self::Error> {
+        self.tokens.push(token);
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
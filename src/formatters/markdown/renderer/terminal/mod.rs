@@ -1,5 +1,6 @@
 mod mode;
 mod utils;
+mod text;
 
 use crossterm::queue;
 use std::io::Write;
@@ -7,84 +8,177 @@ use super::token;
 use super::Renderer;
 use mode::*;
 use utils::InlineStyles;
+use text::TextPrinter;
+use super::super::config::Config;
 
-pub struct TerminalRenderer {
+/// Width used when the size of the terminal is unknown
+const DEFAULT_WIDTH: usize = 80;
+
+/// Width of the terminal, in columns
+pub fn terminal_width() -> usize {
+    crossterm::terminal::size().map(|(w, _)| w as usize).unwrap_or(DEFAULT_WIDTH)
+}
+
+pub struct TerminalRenderer<W: Write = std::io::Stdout> {
+    out: W,
+    /// Width of the output, in columns
+    width: usize,
     mode: Mode,
+    text: TextPrinter,
+    /// Theme of the code blocks, or None if the syntax highlighting is disabled
+    syntax_theme: Option<String>,
+    /// Whether the links are written as OSC 8 hyperlinks
+    hyperlinks: bool,
 }
 
 impl TerminalRenderer {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        Self::with_output(std::io::stdout(), config, terminal_width())
+    }
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    /// Renderer writing to the output, wrapped to the width
+    pub fn with_output(out: W, config: &Config, width: usize) -> Self {
         Self {
+            out,
+            width,
             mode: Mode::default(),
+            text: TextPrinter::new(width, utils::supports_hyperlinks()),
+            syntax_theme: config.syntax_highlighting.then(|| config.syntax_theme.clone()),
+            hyperlinks: utils::supports_hyperlinks(),
         }
     }
+    /// Write the links as OSC 8 hyperlinks or not, instead of detecting the support of the terminal
+    pub fn with_hyperlinks(mut self, hyperlinks: bool) -> Self {
+        self.hyperlinks = hyperlinks;
+        self.text = TextPrinter::new(self.width, hyperlinks);
+        self
+    }
+    pub fn into_output(self) -> W {
+        self.out
+    }
 }
 
-impl Renderer for TerminalRenderer {
+impl<W: Write> Renderer for TerminalRenderer<W> {
     type Error = std::io::Error;
     fn push_token(&mut self, style: token::Token) -> Result<(), Self::Error> {
+        let out = &mut self.out;
         match (style, &mut self.mode) {
-            (token::Token::Text(s), Mode::Text(_)) => {
-                queue!(std::io::stdout(), crossterm::style::Print(s))?;
+            (token::Token::Text(s), Mode::Text(inline_styles)) => {
+                self.text.print(out, &s, inline_styles)?;
             }
             (token::Token::Text(s), Mode::Header(h)) => {
-                h.push_token(token::Token::Text(s))?;
+                h.push_token(out, token::Token::Text(s))?;
             }
             (token::Token::BeginCode, mode @ Mode::Text(_)) => {
-                let code = Code::new();
-                code.init()?;
+                let code = Code::new(self.syntax_theme.clone(), self.width);
+                code.init(out)?;
                 *mode = Mode::Code(code);
             }
             (token @ token::Token::EndCode, mode @ Mode::Code(_)) => {
                 let Mode::Code(code) = mode else { unreachable!() };
-                code.push_token(token)?;
+                code.push_token(out, token)?;
                 *mode = Mode::Text(InlineStyles::default());
             }
+            (token::Token::EndDocument, mode) => { 
+                if let Mode::Code(code) = mode {
+                    // the document ends inside a code block
+                    code.push_token(out, token::Token::EndCode)?;
+                }
+                *mode = Mode::default();
+                queue!(out, crossterm::style::Print("\n"))?;
+            },
             (token, Mode::Code(code)) => {
-                code.push_token(token)?;
+                code.push_token(out, token)?;
             }
             (token::Token::Heading(level), Mode::Text(_)) => {
                 let level = level.into();
-                let header = Header::new(level);
-                header.init()?;
+                let header = Header::new(level, self.width);
+                header.init(out)?;
                 self.mode = Mode::Header(header);
             }
             (token::Token::Heading(_), _) => unreachable!("Header expected only in text mode"),
             (token::Token::Line, Mode::Text(_)) => {
-                utils::draw_line()?;
+                utils::draw_line(out, super::super::theme::current().rule, self.width)?;
             }
             (token::Token::Line, _) => unreachable!("Line expected only in text mode"),
+            (token::Token::ListItem(item), Mode::Text(inline_styles)) => {
+                self.text.print_list_item(out, item, inline_styles)?;
+            }
+            (token::Token::ListItem(_), _) => unreachable!("List item expected only in text mode"),
+            (token::Token::Checkbox(checked), Mode::Text(inline_styles)) => {
+                self.text.print_checkbox(out, checked, inline_styles)?;
+            }
+            (token::Token::Checkbox(_), _) => unreachable!("Checkbox expected only in text mode"),
+            (token::Token::BlockQuote, Mode::Text(inline_styles)) => {
+                self.text.print_quote(out, inline_styles)?;
+            }
+            (token::Token::BlockQuote, _) => unreachable!("Block quote expected only in text mode"),
+            (token::Token::BeginLink(url), Mode::Text(inline_styles)) => {
+                self.text.begin_link(out, url, inline_styles)?;
+            }
+            (token::Token::EndLink, Mode::Text(inline_styles)) => {
+                self.text.end_link(out, inline_styles)?;
+            }
+            (token::Token::Image { alt, url }, Mode::Text(inline_styles)) => {
+                self.text.print(out, &utils::image_label(&alt, &url), inline_styles)?;
+            }
+            (token::Token::Image { alt, url }, Mode::Header(header)) => {
+                header.push_token(out, token::Token::Text(utils::image_label(&alt, &url)))?;
+            }
+            (token::Token::BeginLink(_) | token::Token::EndLink, Mode::Header(_)) => (),
+            (token::Token::TableRow, Mode::Text(_)) => {
+                let mut table = Table::new(self.width, self.hyperlinks);
+                table.push_token(token::Token::TableRow);
+                self.mode = Mode::Table(table);
+            }
+            (token @ (token::Token::TableRow | token::Token::TableCell | token::Token::TableAlignment(_) | token::Token::Text(_) | token::Token::InlineStyle(_)
+                | token::Token::BeginLink(_) | token::Token::EndLink | token::Token::Image { .. }), Mode::Table(table)) => {
+                table.push_token(token);
+            }
+            (token::Token::EndTable, mode @ Mode::Table(_)) => {
+                let Mode::Table(table) = mode else { unreachable!() };
+                table.draw(out)?;
+                *mode = Mode::default();
+            }
+            (token::Token::TableRow | token::Token::TableCell | token::Token::TableAlignment(_) | token::Token::EndTable, _) => unreachable!("Table tokens expected only in table mode"),
             (token::Token::Newline, mode) => {
                 match mode {
-                    Mode::Text(inline_styles) => inline_styles.reset_styles()?,
+                    Mode::Text(inline_styles) => {
+                        if self.text.is_quote() {
+                            // the style of the block quote ends with the line
+                            *inline_styles = InlineStyles::default();
+                        }
+                        inline_styles.reset_styles(out)?
+                    },
                     mode @ Mode::Header(_) => {
                         let inline_styles = InlineStyles::default();
-                        inline_styles.apply_styles()?;
+                        inline_styles.apply_styles(out)?;
                         *mode = Mode::Text(inline_styles);
                     },
-                    Mode::Code(_) => unreachable!()
+                    Mode::Code(_) | Mode::Table(_) => unreachable!()
                 }
-                queue!(std::io::stdout(), crossterm::style::Print("\n"))?;
+                self.text.newline();
+                queue!(out, crossterm::style::Print("\n"))?;
             },
             (token::Token::InlineStyle(token::Marker::Begin(inline_style)), Mode::Text(inline_styles)) => {
-                inline_styles.push_style(inline_style)?;
+                self.text.break_word();
+                inline_styles.push_style(out, inline_style)?;
             }
             (token::Token::InlineStyle(token::Marker::End(_)), Mode::Text(inline_styles)) => {
-                inline_styles.pop_style()?;
+                self.text.break_word();
+                inline_styles.pop_style(out)?;
             }
             (token @ token::Token::InlineStyle(token::Marker::Begin(_) | token::Marker::End(_)), Mode::Header(header)) => {
-                header.push_token(token)?;
+                header.push_token(out, token)?;
             }
             (token::Token::BeginCode, _) => unreachable!("Code beginning expected only in text mode"),
             (token::Token::EndCode, _) => unreachable!("Code ending expected only in code mode"),
-            (token::Token::EndDocument, mode) => { 
-                *mode = Mode::default();
-                queue!(std::io::stdout(), crossterm::style::Print("\n"))?;
-            },
         }
         Ok(())
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        std::io::stdout().flush()
+        self.out.flush()
     }
 }
\ No newline at end of file
@@ -1,38 +1,64 @@
 use super::super::super::token::Token;
 use super::super::utils;
+use super::super::super::super::theme::{self, ColorMode};
+use super::super::super::highlight::Highlighter;
 use crossterm::queue;
-use std::io::Error;
+use std::io::{Error, Write};
 
 #[derive(Default)]
 pub struct Code {
     index: usize,
-    language: Option<String>
+    language: Option<String>,
+    /// Syntax theme, or None if the highlighting is disabled
+    syntax_theme: Option<String>,
+    highlighter: Option<Box<Highlighter>>,
+    line: String,
+    /// Column of the cursor
+    column: usize,
+    /// Whether the current line is wrapped under the gutter
+    wrapped: bool,
+    /// Width of the output, in columns
+    width: usize,
 }
 
 impl Code {
-    pub fn new() -> Self {
+    pub fn new(syntax_theme: Option<String>, width: usize) -> Self {
         Code {
             index: 0,
-            language: None
+            language: None,
+            syntax_theme,
+            highlighter: None,
+            line: String::new(),
+            column: 0,
+            wrapped: false,
+            width,
         }
     }
     #[inline]
-    pub fn init(&self) -> Result<(), Error> {
-        Self::draw_code_separator(false)
+    pub fn init(&self, out: &mut impl Write) -> Result<(), Error> {
+        self.draw_code_separator(out, false)
     }
-    pub fn push_token(&mut self, token: Token) -> Result<(), Error> {
+    pub fn push_token(&mut self, out: &mut impl Write, token: Token) -> Result<(), Error> {
         match token {
             Token::Text(s) if self.index == 0 => {
                 self.push_language(&s);
                 Ok(())
             },
-            Token::Text(s) => queue!(std::io::stdout(), crossterm::style::Print(s)),
+            Token::Text(s) => {
+                self.line.push_str(&s);
+                self.print_wrapped(out, &s)
+            },
             Token::Newline => {
+                if self.index == 0 {
+                    self.init_highlighter();
+                } else {
+                    self.highlight_line(out)?;
+                }
                 self.index += 1;
-                queue!(std::io::stdout(), crossterm::style::Print("\n"))?;
-                self.draw_newline()
+                queue!(out, crossterm::style::Print("\n"))?;
+                self.draw_newline(out)
             },
-            Token::EndCode => Self::draw_code_separator(true),
+            Token::EndCode => self.draw_code_separator(out, true),
             _ => unreachable!("Token not supported in code mode: {:?}", token),
         }
     }
@@ -42,30 +68,103 @@ impl Code {
             Some(s) => s.push_str(word)
         }
     }
+    fn init_highlighter(&mut self) {
+        use std::io::IsTerminal;
+        let colors = match theme::current().color_mode {
+            ColorMode::Auto => std::io::stdout().is_terminal(),
+            ColorMode::Never => false,
+            ColorMode::Always => true,
+        };
+        if !colors {
+            return;
+        }
+        let (Some(language), Some(theme)) = (&self.language, &self.syntax_theme) else { return };
+        self.highlighter = Highlighter::new(language, theme).map(Box::new);
+    }
+    /// Draw again the current line with syntax highlighting
+    ///
+    /// The line is drawn as plain text while it is streamed, then highlighted once complete.
+    fn highlight_line(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        let Some(highlighter) = self.highlighter.as_mut() else { 
+            self.line.clear();
+            return Ok(());
+        };
+        let line = std::mem::take(&mut self.line) + "\n";
+        let ranges = highlighter.highlight(&line)?;
+        if line.contains('\t') || self.wrapped {
+            // the line is drawn on several rows, can't draw it again
+            return Ok(());
+        }
+        queue!(out, 
+            crossterm::cursor::MoveToColumn(Self::text_column()),
+        )?;
+        highlighter.draw(out, &ranges)?;
+        queue!(out, 
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine),
+        )
+    }
+    /// Print code, wrapping the lines too long under the gutter
+    fn print_wrapped(&mut self, out: &mut impl Write, text: &str) -> Result<(), Error> {
+        use unicode_width::UnicodeWidthChar;
+        let width = self.width;
+        let text_column = Self::text_column() as usize;
+        let mut segment = String::with_capacity(text.len());
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if self.column + char_width > width && self.column > text_column {
+                queue!(out, crossterm::style::Print(std::mem::take(&mut segment)))?;
+                queue!(out, crossterm::style::Print("\n"))?;
+                self.draw_gutter(out, None)?;
+                self.wrapped = true;
+            }
+            segment.push(c);
+            self.column += char_width;
+        }
+        queue!(out, crossterm::style::Print(segment))
+    }
     const fn counter_space() -> u16 {
         (utils::CODE_BLOCK_COUNTER_SPACE + utils::CODE_BLOCK_MARGIN * 2) as _
     }
-    fn draw_code_separator(sens: bool /* false: down, true: up */) -> Result<(), Error> {
-        queue!(std::io::stdout(), 
+    const fn text_column() -> u16 {
+        Self::counter_space() + 1 + utils::CODE_BLOCK_MARGIN as u16
+    }
+    fn draw_code_separator(&self, out: &mut impl Write, sens: bool /* false: down, true: up */) -> Result<(), Error> {
+        queue!(out, 
             crossterm::cursor::MoveToColumn(0)
         )?;
-        utils::draw_line()?;
-        crossterm::terminal::enable_raw_mode()?;
-        queue!(std::io::stdout(), 
+        let theme = theme::current();
+        utils::draw_line(out, theme.code_gutter, self.width)?;
+        theme.code_gutter.apply(out)?;
+        queue!(out, 
             crossterm::cursor::MoveToColumn(Self::counter_space() as _),
-            crossterm::style::Print(utils::CODE_BLOCK_LINE_CHAR[2+sens as usize]),
+            crossterm::style::Print(theme.chars.lines[2+sens as usize]),
+            crossterm::style::ResetColor,
+            crossterm::style::SetAttribute(crossterm::style::Attribute::Reset),
             crossterm::cursor::MoveToColumn(0)
         )?;
-        crossterm::terminal::disable_raw_mode()?;
         Ok(())
     }
-    fn draw_newline(&self) -> Result<(), Error> {
-        let line = format!("{3}{0:0>1$}{3}{2}{3}", 
-            self.index, 
-            utils::CODE_BLOCK_COUNTER_SPACE, 
-            utils::CODE_BLOCK_LINE_CHAR[1],
+    fn draw_newline(&mut self, out: &mut impl Write) -> Result<(), Error> {
+        self.wrapped = false;
+        self.draw_gutter(out, Some(self.index))
+    }
+    /// Draw the line number, or a blank gutter for the wrapped lines
+    fn draw_gutter(&mut self, out: &mut impl Write, index: Option<usize>) -> Result<(), Error> {
+        let counter = match index {
+            Some(index) => format!("{0:0>1$}", index, utils::CODE_BLOCK_COUNTER_SPACE),
+            None => utils::repeat_char(' ', utils::CODE_BLOCK_COUNTER_SPACE),
+        };
+        let line = format!("{2}{0}{2}{1}{2}", 
+            counter, 
+            theme::current().chars.lines[1],
             utils::repeat_char(' ', utils::CODE_BLOCK_MARGIN)
         );
-        queue!(std::io::stdout(), crossterm::style::Print(line))
+        self.column = Self::text_column() as usize;
+        theme::current().code_gutter.apply(out)?;
+        queue!(out,
+            crossterm::style::Print(line),
+            crossterm::style::ResetColor,
+            crossterm::style::SetAttribute(crossterm::style::Attribute::Reset),
+        )
     }
 }
@@ -1,8 +1,10 @@
 mod header;
 mod code;
+mod table;
 
 pub use header::Header;
 pub use code::Code;
+pub use table::Table;
 use super::InlineStyles;
 
 
@@ -10,6 +12,7 @@ pub enum Mode {
     Text(InlineStyles),
     Code(Code),
     Header(Header),
+    Table(Table),
 }
 
 impl Default for Mode {
@@ -0,0 +1,238 @@
+use super::InlineStyles;
+use super::super::utils;
+use super::super::super::super::theme::{self, Style};
+use super::super::super::token::{self, Token};
+use crossterm::{queue, style::*};
+use std::io::{Error, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Narrowest width of a column when the table is shrunk to fit in the terminal
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Character of a cell, with the index of its style
+type StyledChar = (char, usize);
+
+/// Inline styles and hyperlink of characters of the cells
+#[derive(PartialEq, Default, Clone)]
+struct CellStyle {
+    inline_styles: Vec<token::InlineStyleToken>,
+    link: Option<String>,
+}
+
+#[derive(Default)]
+struct Cell {
+    chars: Vec<StyledChar>,
+}
+
+impl Cell {
+    fn width(&self) -> usize {
+        self.chars.iter().map(|(c, _)| c.width().unwrap_or(0)).sum()
+    }
+    /// Wrap the content of the cell, preferably on spaces
+    fn wrap(&self, width: usize) -> Vec<Vec<StyledChar>> {
+        let mut lines = Vec::new();
+        let mut line = Vec::<StyledChar>::new();
+        let mut line_width = 0;
+        let mut last_space = None;
+        for &(c, style) in &self.chars {
+            let char_width = c.width().unwrap_or(0);
+            if c == ' ' && line_width + char_width > width && !line.is_empty() {
+                // the line ends right before this space
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+                last_space = None;
+                continue;
+            }
+            while line_width + char_width > width && !line.is_empty() {
+                let rest = match last_space.take() {
+                    Some(i) => {
+                        let rest = line.split_off(i + 1);
+                        line.pop();
+                        rest
+                    }
+                    None => Vec::new(),
+                };
+                lines.push(std::mem::replace(&mut line, rest));
+                line_width = line.iter().map(|(c, _)| c.width().unwrap_or(0)).sum();
+                // the remainder can be split again on its own spaces
+                last_space = line.iter().rposition(|(c, _)| *c == ' ');
+            }
+            if c == ' ' {
+                if line.is_empty() && !lines.is_empty() {
+                    continue;
+                }
+                last_space = Some(line.len());
+            }
+            line.push((c, style));
+            line_width += char_width;
+        }
+        lines.push(line);
+        lines
+    }
+}
+
+/// Table buffered until its end, the width of the columns depends on every row
+#[derive(Default)]
+pub struct Table {
+    alignments: Vec<token::Alignment>,
+    /// Number of rows of the header, known once the alignment row is parsed
+    header_rows: usize,
+    rows: Vec<Vec<Cell>>,
+    /// Styles used in the cells
+    styles: Vec<CellStyle>,
+    current_style: CellStyle,
+    /// Width of the output, in columns
+    width: usize,
+    /// Whether the links are written as OSC 8 hyperlinks
+    hyperlinks: bool,
+}
+
+impl Table {
+    pub fn new(width: usize, hyperlinks: bool) -> Self {
+        Self { width, hyperlinks, ..Default::default() }
+    }
+    pub fn push_token(&mut self, token: Token) {
+        match token {
+            Token::TableRow => self.rows.push(Vec::new()),
+            Token::TableCell => {
+                self.current_style = CellStyle::default();
+                if let Some(row) = self.rows.last_mut() {
+                    row.push(Cell::default());
+                }
+            }
+            Token::TableAlignment(alignments) => {
+                self.alignments = alignments;
+                self.header_rows = self.rows.len();
+            }
+            Token::Text(s) => self.push_text(&s),
+            Token::InlineStyle(token::Marker::Begin(style)) => self.current_style.inline_styles.push(style),
+            Token::InlineStyle(token::Marker::End(_)) => { self.current_style.inline_styles.pop(); }
+            Token::BeginLink(url) => {
+                self.current_style.inline_styles.push(token::InlineStyleToken::Link);
+                self.current_style.link = Some(url);
+            }
+            Token::EndLink => {
+                self.current_style.inline_styles.pop();
+                if let Some(url) = self.current_style.link.take() {
+                    if !self.hyperlinks {
+                        self.push_text(&format!(" ({})", url));
+                    }
+                }
+            }
+            Token::Image { alt, url } => self.push_text(&utils::image_label(&alt, &url)),
+            _ => unreachable!("Token not supported in table mode: {:?}", token),
+        }
+    }
+    fn push_text(&mut self, text: &str) {
+        let style = self.style_index();
+        if let Some(cell) = self.rows.last_mut().and_then(|row| row.last_mut()) {
+            cell.chars.extend(text.chars().map(|c| (c, style)));
+        }
+    }
+    fn style_index(&mut self) -> usize {
+        match self.styles.iter().position(|s| s == &self.current_style) {
+            Some(index) => index,
+            None => {
+                self.styles.push(self.current_style.clone());
+                self.styles.len() - 1
+            }
+        }
+    }
+    /// Width of the columns, shrunk until the table fits in the width of the output
+    fn column_widths(&self) -> Vec<usize> {
+        let column_count = self.rows.iter().map(Vec::len).max().unwrap_or(0).max(self.alignments.len());
+        let mut widths = vec![1; column_count];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.width());
+            }
+        }
+        // borders and margins: "│ a │ b │"
+        let available = self.width.saturating_sub(column_count * (1 + utils::CODE_BLOCK_MARGIN * 2) + 1);
+        while widths.iter().sum::<usize>() > available {
+            let Some(widest) = widths.iter_mut().filter(|w| **w > MIN_COLUMN_WIDTH).max_by_key(|w| **w) else { break };
+            *widest -= 1;
+        }
+        widths
+    }
+    pub fn draw(&self, out: &mut impl Write) -> Result<(), Error> {
+        let widths = self.column_widths();
+        Self::draw_border(out, &widths, 0)?;
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 && index == self.header_rows {
+                Self::draw_border(out, &widths, 1)?;
+            }
+            self.draw_row(out, row, &widths, index < self.header_rows)?;
+        }
+        Self::draw_border(out, &widths, 2)
+    }
+    /// Draw a horizontal border: 0 for the top, 1 below the header, 2 for the bottom
+    fn draw_border(out: &mut impl Write, widths: &[usize], position: usize) -> Result<(), Error> {
+        let chars = theme::current().chars;
+        let [left, middle, right] = chars.table[position];
+        let line = widths.iter()
+            .map(|w| utils::repeat_char(chars.lines[0], w + utils::CODE_BLOCK_MARGIN * 2))
+            .collect::<Vec<_>>()
+            .join(&middle.to_string());
+        queue!(out, Print(format!("{}{}{}\n", left, line, right)))
+    }
+    fn draw_row(&self, out: &mut impl Write, row: &[Cell], widths: &[usize], is_header: bool) -> Result<(), Error> {
+        let empty_cell = Cell::default();
+        let cells = widths.iter().enumerate()
+            .map(|(i, width)| row.get(i).unwrap_or(&empty_cell).wrap(*width))
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+        let vertical_line = theme::current().chars.lines[1];
+        let default_style = if is_header { theme::current().strong } else { Style::default() };
+        let mut inline_styles = InlineStyles::new(default_style);
+        let margin = utils::repeat_char(' ', utils::CODE_BLOCK_MARGIN);
+        for line_index in 0..height {
+            for (column, lines) in cells.iter().enumerate() {
+                let line = lines.get(line_index).map(Vec::as_slice).unwrap_or_default();
+                let padding = widths[column].saturating_sub(line.iter().map(|(c, _)| c.width().unwrap_or(0)).sum());
+                let (before, after) = match self.alignments.get(column) {
+                    Some(token::Alignment::Right) => (padding, 0),
+                    Some(token::Alignment::Center) => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+                queue!(out,
+                    Print(vertical_line),
+                    Print(&margin),
+                    Print(utils::repeat_char(' ', before)),
+                )?;
+                self.draw_line(out, line, &mut inline_styles)?;
+                InlineStyles::default().apply_styles(out)?;
+                queue!(out,
+                    Print(utils::repeat_char(' ', after)),
+                    Print(&margin),
+                )?;
+            }
+            queue!(out, Print(format!("{}\n", vertical_line)))?;
+        }
+        Ok(())
+    }
+    fn draw_line(&self, out: &mut impl Write, line: &[StyledChar], inline_styles: &mut InlineStyles) -> Result<(), Error> {
+        inline_styles.reset_styles(out)?;
+        let mut current_style = None;
+        let mut current_link = None;
+        let mut text = String::new();
+        for &(c, style) in line {
+            if current_style != Some(style) {
+                queue!(out, Print(std::mem::take(&mut text)))?;
+                let style = &self.styles[style];
+                inline_styles.set_styles(out, style.inline_styles.clone())?;
+                if current_link != style.link.as_ref() && self.hyperlinks {
+                    queue!(out, Print(utils::hyperlink(style.link.as_deref().unwrap_or_default())))?;
+                }
+                current_link = style.link.as_ref();
+            }
+            current_style = Some(style);
+            text.push(c);
+        }
+        queue!(out, Print(text))?;
+        if current_link.is_some() && self.hyperlinks {
+            queue!(out, Print(utils::hyperlink("")))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use super::token;
+use super::utils::{self, InlineStyles};
+use super::super::super::theme;
+use crossterm::{queue, style::Print};
+use std::io::{Error, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Printer of the text mode, wrapping the words to the width of the output
+#[derive(Default)]
+pub struct TextPrinter {
+    /// Width of the output, in columns
+    width: usize,
+    /// Column of the cursor
+    column: usize,
+    /// Beginning of the wrapped lines: quote bars and list indentation
+    hanging_prefix: String,
+    /// Last word printed on the line, moved to the next line if it overflows
+    word: String,
+    /// Column of the beginning of the last word
+    word_start: usize,
+    /// Url of the current link
+    link_url: Option<String>,
+    /// Whether the links are written as OSC 8 hyperlinks, else their url follows their text
+    hyperlinks: bool,
+}
+
+impl TextPrinter {
+    pub fn new(width: usize, hyperlinks: bool) -> Self {
+        Self { width, hyperlinks, ..Default::default() }
+    }
+    /// Print text, wrapping the words which don't fit on the line
+    pub fn print(&mut self, out: &mut impl Write, text: &str, inline_styles: &InlineStyles) -> Result<(), Error> {
+        let width = self.width;
+        let mut segment = String::with_capacity(text.len());
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or(0);
+            let overflows = self.column + char_width > width;
+            if c.is_whitespace() {
+                self.word.clear();
+                if overflows {
+                    // the line is wrapped on the space
+                    queue!(out, Print(std::mem::take(&mut segment)))?;
+                    self.wrap(out, inline_styles)?;
+                    continue;
+                }
+                segment.push(c);
+                self.column += char_width;
+                self.word_start = self.column;
+                continue;
+            }
+            if overflows {
+                queue!(out, Print(std::mem::take(&mut segment)))?;
+                let prefix_width = self.hanging_prefix.width();
+                let word_width = self.word.width();
+                if !self.word.is_empty() && self.word_start > prefix_width && prefix_width + word_width + char_width <= width {
+                    // move the beginning of the word to the next line
+                    queue!(out,
+                        crossterm::cursor::MoveToColumn(self.word_start as _),
+                        crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine),
+                    )?;
+                    self.wrap(out, inline_styles)?;
+                    segment.push_str(&self.word);
+                    self.column += word_width;
+                } else {
+                    self.wrap(out, inline_styles)?;
+                    self.word.clear();
+                }
+            }
+            if self.word.is_empty() {
+                self.word_start = self.column;
+            }
+            segment.push(c);
+            self.word.push(c);
+            self.column += char_width;
+        }
+        queue!(out, Print(segment))
+    }
+    /// Go to the next line, beginning with the hanging prefix
+    fn wrap(&mut self, out: &mut impl Write, inline_styles: &InlineStyles) -> Result<(), Error> {
+        // the prefix has the style of the quote bars, or no style if it is only indentation
+        if self.is_quote() {
+            InlineStyles::new(theme::current().quote).apply_styles(out)?;
+        } else {
+            InlineStyles::default().apply_styles(out)?;
+        }
+        queue!(out, Print("\n"), Print(&self.hanging_prefix))?;
+        inline_styles.apply_styles(out)?;
+        self.column = self.hanging_prefix.width();
+        self.word_start = self.column;
+        Ok(())
+    }
+    /// The styled parts of a word are not moved together
+    pub fn break_word(&mut self) {
+        self.word.clear();
+    }
+    pub fn newline(&mut self) {
+        self.column = 0;
+        self.hanging_prefix.clear();
+        self.word.clear();
+        self.word_start = 0;
+    }
+    pub fn is_quote(&self) -> bool {
+        self.hanging_prefix.contains(theme::current().chars.lines[1])
+    }
+    pub fn print_list_item(&mut self, out: &mut impl Write, item: token::ListItem, inline_styles: &InlineStyles) -> Result<(), Error> {
+        let level: usize = item.level.into();
+        let bullets = &theme::current().chars.bullets;
+        let marker = match item.kind {
+            token::ListKind::Unordered => bullets[level % bullets.len()].to_string(),
+            token::ListKind::Ordered(n) => format!("{}.", n),
+        };
+        let prefix = format!("{}{} ", utils::repeat_char(' ', level * utils::LIST_INDENT), marker);
+        self.print(out, &prefix, inline_styles)?;
+        self.extend_hanging_prefix();
+        Ok(())
+    }
+    pub fn print_checkbox(&mut self, out: &mut impl Write, checked: bool, inline_styles: &InlineStyles) -> Result<(), Error> {
+        self.print(out, &format!("{} ", theme::current().chars.checkboxes[checked as usize]), inline_styles)?;
+        self.extend_hanging_prefix();
+        Ok(())
+    }
+    /// Indent the wrapped lines up to the current column
+    fn extend_hanging_prefix(&mut self) {
+        let prefix_width = self.hanging_prefix.width();
+        self.hanging_prefix.push_str(&utils::repeat_char(' ', self.column.saturating_sub(prefix_width)));
+        self.word.clear();
+    }
+    pub fn print_quote(&mut self, out: &mut impl Write, inline_styles: &mut InlineStyles) -> Result<(), Error> {
+        let theme = theme::current();
+        *inline_styles = InlineStyles::new(theme.quote);
+        inline_styles.apply_styles(out)?;
+        let bar = format!("{} ", theme.chars.lines[1]);
+        self.print(out, &bar, inline_styles)?;
+        self.hanging_prefix.push_str(&bar);
+        Ok(())
+    }
+    pub fn begin_link(&mut self, out: &mut impl Write, url: String, inline_styles: &mut InlineStyles) -> Result<(), Error> {
+        if self.hyperlinks {
+            queue!(out, Print(utils::hyperlink(&url)))?;
+        }
+        self.link_url = Some(url);
+        self.break_word();
+        inline_styles.push_style(out, token::InlineStyleToken::Link)
+    }
+    pub fn end_link(&mut self, out: &mut impl Write, inline_styles: &mut InlineStyles) -> Result<(), Error> {
+        inline_styles.pop_style(out)?;
+        self.break_word();
+        let Some(url) = self.link_url.take() else { return Ok(()) };
+        if self.hyperlinks {
+            queue!(out, Print(utils::hyperlink("")))
+        } else {
+            self.print(out, &format!(" ({})", url), inline_styles)
+        }
+    }
+}
@@ -1,70 +1,104 @@
 use once_cell::sync::Lazy;
 use super::{token, queue};
-use std::io::Error;
+use super::super::super::theme::{self, Style};
+use std::io::{Error, Write};
 
 
 pub const CODE_BLOCK_COUNTER_SPACE: usize = 3;
-pub const CODE_BLOCK_LINE_CHAR: [char; 4] = ['─', '│', '┬', '┴'];
 pub const CODE_BLOCK_MARGIN: usize = 1;
-// pub const TEXT_BULLETS: [char; 4] = ['•', '◦', '▪', '▫'];
+pub const LIST_INDENT: usize = 2;
 
+#[derive(Default)]
 pub struct InlineStyles {
     styles: Vec<token::InlineStyleToken>,
-    default_style: crossterm::style::Attributes,
+    default_style: Style,
 }
 
-impl Default for InlineStyles {
-    fn default() -> Self {
-        Self {
-            styles: Vec::new(),
-            default_style: crossterm::style::Attribute::Reset.into(),
-        }
-    }
-}
 impl InlineStyles {
-    pub fn new(default_style: crossterm::style::Attributes) -> Self {
+    pub fn new(default_style: Style) -> Self {
         Self { styles: Vec::new(), default_style }
     }
-    fn apply_inline_style(&self, inline_style: &token::InlineStyleToken) -> Result<(), Error> {
+    fn apply_inline_style(&self, out: &mut impl Write, inline_style: &token::InlineStyleToken) -> Result<(), Error> {
         use crossterm::style::*;
+        let theme = theme::current();
         match inline_style {
-            token::InlineStyleToken::OneStar => queue!(std::io::stdout(), SetAttribute(Attribute::Italic)),
-            token::InlineStyleToken::TwoStars => queue!(std::io::stdout(), SetAttribute(Attribute::Bold)),
-            token::InlineStyleToken::ThreeStars => queue!(std::io::stdout(), SetAttribute(Attribute::Italic), SetAttribute(Attribute::Bold)),
-            token::InlineStyleToken::OneDash => queue!(std::io::stdout(), SetAttribute(Attribute::Italic)),
-            token::InlineStyleToken::TwoDashes => queue!(std::io::stdout(), SetAttribute(Attribute::Underlined)),
-            token::InlineStyleToken::OneQuote => queue!(std::io::stdout(), SetForegroundColor(Color::Yellow)),
+            token::InlineStyleToken::OneStar | token::InlineStyleToken::OneDash => theme.emphasis.apply(out),
+            token::InlineStyleToken::TwoStars => theme.strong.apply(out),
+            token::InlineStyleToken::ThreeStars => {
+                theme.emphasis.apply(out)?;
+                theme.strong.apply(out)
+            }
+            token::InlineStyleToken::TwoDashes => queue!(out, SetAttribute(Attribute::Underlined)),
+            token::InlineStyleToken::OneQuote | token::InlineStyleToken::TwoQuotes => theme.inline_code.apply(out),
+            token::InlineStyleToken::TwoTildes => queue!(out, SetAttribute(Attribute::CrossedOut)),
+            token::InlineStyleToken::OneDollar => theme.math.apply(out),
+            token::InlineStyleToken::Link => theme.link.apply(out),
         }
     }
-    pub fn apply_styles(&self) -> Result<(), Error> {
-        queue!(std::io::stdout(), 
+    pub fn apply_styles(&self, out: &mut impl Write) -> Result<(), Error> {
+        queue!(out, 
             crossterm::style::ResetColor, 
             crossterm::style::SetAttribute(crossterm::style::Attribute::Reset), 
-            crossterm::style::SetAttributes(self.default_style)
         )?;
+        self.default_style.apply(out)?;
         for c in self.styles.iter() {
-            self.apply_inline_style(c)?;
+            self.apply_inline_style(out, c)?;
         }
 
         Ok(())
     }
-    pub fn push_style(&mut self, style: token::InlineStyleToken) -> Result<(), Error> {
+    pub fn push_style(&mut self, out: &mut impl Write, style: token::InlineStyleToken) -> Result<(), Error> {
         self.styles.push(style);
-        self.apply_styles()?;
+        self.apply_styles(out)?;
         Ok(())
     }
-    pub fn pop_style(&mut self) -> Result<(), Error> {
+    pub fn pop_style(&mut self, out: &mut impl Write) -> Result<(), Error> {
         self.styles.pop();
-        self.apply_styles()?;
+        self.apply_styles(out)?;
         Ok(())
     }
-    pub fn reset_styles(&mut self) -> Result<(), Error> {
+    pub fn set_styles(&mut self, out: &mut impl Write, styles: Vec<token::InlineStyleToken>) -> Result<(), Error> {
+        self.styles = styles;
+        self.apply_styles(out)
+    }
+    pub fn reset_styles(&mut self, out: &mut impl Write) -> Result<(), Error> {
         self.styles.clear();
-        self.apply_styles()?;
+        self.apply_styles(out)?;
         Ok(())
     }
 }
 
+/// Whether the terminal supports OSC 8 hyperlinks
+///
+/// `FORCE_HYPERLINK=1` or `FORCE_HYPERLINK=0` overrides the detection.
+pub fn supports_hyperlinks() -> bool {
+    static SUPPORTED: Lazy<bool> = Lazy::new(|| {
+        use std::io::IsTerminal;
+        if let Ok(force) = std::env::var("FORCE_HYPERLINK") {
+            return force != "0";
+        }
+        if !std::io::stdout().is_terminal() {
+            return false;
+        }
+        let env = |name: &str| std::env::var(name).unwrap_or_default();
+        matches!(env("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty")
+            || env("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000)
+            || !env("KONSOLE_VERSION").is_empty()
+            || !env("WT_SESSION").is_empty()
+            || ["kitty", "alacritty", "foot", "ghostty"].iter().any(|t| env("TERM").contains(t))
+    });
+    *SUPPORTED
+}
+
+/// Escape sequence opening an OSC 8 hyperlink, or closing it if the url is empty
+pub fn hyperlink(url: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\", url)
+}
+
+pub fn image_label(alt: &str, url: &str) -> String {
+    format!("[image: {}]({})", alt, url)
+}
+
 pub fn repeat_char(c: char, n: usize) -> String {
     // let mut s = String::with_capacity(n);
     let mut s = String::new();
@@ -75,11 +109,11 @@ pub fn repeat_char(c: char, n: usize) -> String {
 }
 
 #[inline]
-pub fn draw_line() -> Result<(), Error> {
-    static LINE_STRING: Lazy<String> = Lazy::new(|| {
-        repeat_char(CODE_BLOCK_LINE_CHAR[0], CODE_BLOCK_MARGIN.max(crossterm::terminal::size().unwrap_or_default().0 as usize))
-    });
-    queue!(std::io::stdout(), 
-        crossterm::style::Print(&*LINE_STRING)
+pub fn draw_line(out: &mut impl Write, style: Style, width: usize) -> Result<(), Error> {
+    style.apply(out)?;
+    queue!(out, 
+        crossterm::style::Print(repeat_char(theme::current().chars.lines[0], CODE_BLOCK_MARGIN.max(width))),
+        crossterm::style::ResetColor,
+        crossterm::style::SetAttribute(crossterm::style::Attribute::Reset),
     )
 }
\ No newline at end of file
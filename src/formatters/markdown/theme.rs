@@ -0,0 +1,317 @@
+//! Styles and box drawing characters of the terminal renderer
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::io::Write;
+use thiserror::Error;
+
+const COLORS: [(&str, Color); 16] = [
+    ("black", Color::Black), ("dark_grey", Color::DarkGrey), ("red", Color::Red), ("dark_red", Color::DarkRed),
+    ("green", Color::Green), ("dark_green", Color::DarkGreen), ("yellow", Color::Yellow), ("dark_yellow", Color::DarkYellow),
+    ("blue", Color::Blue), ("dark_blue", Color::DarkBlue), ("magenta", Color::Magenta), ("dark_magenta", Color::DarkMagenta),
+    ("cyan", Color::Cyan), ("dark_cyan", Color::DarkCyan), ("white", Color::White), ("grey", Color::Grey),
+];
+
+const ATTRIBUTES: [(&str, Attribute); 6] = [
+    ("bold", Attribute::Bold), ("dim", Attribute::Dim), ("italic", Attribute::Italic),
+    ("underlined", Attribute::Underlined), ("reverse", Attribute::Reverse), ("crossed_out", Attribute::CrossedOut),
+];
+
+#[derive(Error, Debug)]
+pub enum StyleError {
+    #[error("unknown color or attribute `{0}`")]
+    UnknownWord(String),
+}
+
+/// Style written as words: attributes, foreground color and background color prefixed by `on_`
+///
+/// Ex: `bold underlined`, `yellow`, `italic #ff8800 on_dark_grey`. The colors can also be
+/// an index of the 256 colors palette.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Style(ContentStyle);
+
+impl Style {
+    fn parse_color(word: &str) -> Option<Color> {
+        if let Some(hex) = word.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            return Some(Color::Rgb { r: component(0)?, g: component(2)?, b: component(4)? });
+        }
+        if let Ok(index) = word.parse::<u8>() {
+            return Some(Color::AnsiValue(index));
+        }
+        COLORS.iter().find(|(name, _)| *name == word).map(|(_, color)| *color)
+    }
+    fn color_name(color: Color) -> String {
+        match color {
+            Color::Rgb { r, g, b } => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            Color::AnsiValue(index) => index.to_string(),
+            color => COLORS.iter().find(|(_, c)| *c == color).map(|(name, _)| name.to_string()).unwrap_or_default(),
+        }
+    }
+    fn without_colors(self) -> Self {
+        Self(ContentStyle { attributes: self.0.attributes, ..Default::default() })
+    }
+    /// Queue the commands setting the style, over the current one
+    pub fn apply(&self, out: &mut impl Write) -> std::io::Result<()> {
+        use crossterm::style::{SetAttributes, SetBackgroundColor, SetForegroundColor};
+        crossterm::queue!(out, SetAttributes(self.0.attributes))?;
+        if let Some(color) = self.0.foreground_color {
+            crossterm::queue!(out, SetForegroundColor(color))?;
+        }
+        if let Some(color) = self.0.background_color {
+            crossterm::queue!(out, SetBackgroundColor(color))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Attribute> for Style {
+    fn from(attribute: Attribute) -> Self {
+        Self(ContentStyle { attributes: attribute.into(), ..Default::default() })
+    }
+}
+
+impl TryFrom<&str> for Style {
+    type Error = StyleError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut style = ContentStyle::default();
+        for word in value.split_whitespace() {
+            if let Some(color) = word.strip_prefix("on_").and_then(Self::parse_color) {
+                style.background_color = Some(color);
+            } else if let Some(color) = Self::parse_color(word) {
+                style.foreground_color = Some(color);
+            } else if let Some((_, attribute)) = ATTRIBUTES.iter().find(|(name, _)| *name == word) {
+                style.attributes.set(*attribute);
+            } else {
+                return Err(StyleError::UnknownWord(word.to_string()));
+            }
+        }
+        Ok(Self(style))
+    }
+}
+
+impl TryFrom<String> for Style {
+    type Error = StyleError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl From<Style> for String {
+    fn from(style: Style) -> Self {
+        let mut words = ATTRIBUTES.iter()
+            .filter(|(_, attribute)| style.0.attributes.has(*attribute))
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        words.extend(style.0.foreground_color.map(Style::color_name));
+        words.extend(style.0.background_color.map(|color| format!("on_{}", Style::color_name(color))));
+        words.join(" ")
+    }
+}
+
+/// Written as a string of words
+impl JsonSchema for Style {
+    fn schema_name() -> String {
+        "Style".to_string()
+    }
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        let mut schema = String::json_schema(gen).into_object();
+        schema.metadata().description = Some("Attributes, foreground color and background color prefixed by `on_` (ex: `italic #ff8800 on_dark_grey`)".to_string());
+        schema.into()
+    }
+}
+
+/// Styles used when they are not set in the configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    #[default]
+    Dark,
+    Light,
+    /// Only attributes like bold or italic, used if `NO_COLOR` is set
+    NoColor,
+}
+
+/// Theme section of the configuration, each style overrides the one of the preset
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub preset: Preset,
+    /// Draw the boxes, bullets and checkboxes with ASCII characters only
+    pub ascii: bool,
+    /// Style of each heading level, the last one is used for the deeper levels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headings: Option<Vec<Style>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emphasis: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strong: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_code: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub math: Option<Style>,
+    /// Line numbers and separators of the code blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_gutter: Option<Style>,
+    /// Horizontal rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<Style>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<Style>,
+}
+
+/// Characters drawing the boxes, bullets and checkboxes
+pub struct Charset {
+    /// Horizontal line, vertical line, then the junctions of the gutter with the top and bottom separators
+    pub lines: [char; 4],
+    /// Corners and junctions of the top, middle and bottom borders of the tables
+    pub table: [[char; 3]; 3],
+    pub bullets: [char; 4],
+    /// Unchecked then checked
+    pub checkboxes: [&'static str; 2],
+}
+
+static UNICODE_CHARSET: Charset = Charset {
+    lines: ['─', '│', '┬', '┴'],
+    table: [['┌', '┬', '┐'], ['├', '┼', '┤'], ['└', '┴', '┘']],
+    bullets: ['•', '◦', '▪', '▫'],
+    checkboxes: ["☐", "☑"],
+};
+
+static ASCII_CHARSET: Charset = Charset {
+    lines: ['-', '|', '+', '+'],
+    table: [['+', '+', '+'], ['+', '+', '+'], ['+', '+', '+']],
+    bullets: ['*', '-', '+', '-'],
+    checkboxes: ["[ ]", "[x]"],
+};
+
+/// Use of the colors, from the `NO_COLOR` and `CLICOLOR_FORCE` environment variables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    /// Colors only if the output is a terminal
+    Auto,
+    Never,
+    /// Colors even if the output is not a terminal
+    Always,
+}
+
+impl ColorMode {
+    fn from_env() -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        if env("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+            Self::Always
+        } else if env("NO_COLOR").is_some() {
+            Self::Never
+        } else {
+            Self::Auto
+        }
+    }
+}
+
+pub struct Theme {
+    pub headings: Vec<Style>,
+    pub emphasis: Style,
+    pub strong: Style,
+    pub inline_code: Style,
+    pub math: Style,
+    pub code_gutter: Style,
+    pub rule: Style,
+    pub quote: Style,
+    pub link: Style,
+    pub chars: &'static Charset,
+    pub color_mode: ColorMode,
+}
+
+impl Theme {
+    fn preset(preset: Preset) -> Self {
+        let style = |s: &str| Style::try_from(s).expect("Invalid style of a preset");
+        let (headings, inline_code, math, link) = match preset {
+            Preset::Dark => (vec![style("bold reverse")], style("yellow"), style("cyan"), style("underlined blue")),
+            Preset::Light => (
+                vec![style("bold reverse dark_blue"), style("bold reverse dark_cyan")],
+                style("dark_red"),
+                style("dark_magenta"),
+                style("underlined dark_blue"),
+            ),
+            Preset::NoColor => (vec![style("bold reverse")], style("bold"), style("italic"), style("underlined")),
+        };
+        Self {
+            headings,
+            emphasis: style("italic"),
+            strong: style("bold"),
+            inline_code,
+            math,
+            code_gutter: Style::default(),
+            rule: Style::default(),
+            quote: style("dim"),
+            link,
+            chars: &UNICODE_CHARSET,
+            color_mode: ColorMode::Auto,
+        }
+    }
+    pub fn from_config(config: &Config) -> Self {
+        let color_mode = ColorMode::from_env();
+        let preset = if color_mode == ColorMode::Never { Preset::NoColor } else { config.preset };
+        let mut theme = Self::preset(preset);
+        if let Some(headings) = config.headings.as_ref().filter(|headings| !headings.is_empty()) {
+            theme.headings = headings.clone();
+        }
+        let config_styles = [
+            config.emphasis, config.strong, config.inline_code, config.math,
+            config.code_gutter, config.rule, config.quote, config.link,
+        ];
+        for (style, config_style) in theme.styles_mut().zip(config_styles) {
+            if let Some(config_style) = config_style {
+                *style = config_style;
+            }
+        }
+        if color_mode == ColorMode::Never {
+            // the overrides can't add colors
+            for style in theme.styles_mut() {
+                *style = style.without_colors();
+            }
+            for style in theme.headings.iter_mut() {
+                *style = style.without_colors();
+            }
+        }
+        if config.ascii {
+            theme.chars = &ASCII_CHARSET;
+        }
+        theme.color_mode = color_mode;
+        theme
+    }
+    /// Styles other than the headings, in the order of the configuration
+    fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        [
+            &mut self.emphasis, &mut self.strong, &mut self.inline_code, &mut self.math,
+            &mut self.code_gutter, &mut self.rule, &mut self.quote, &mut self.link,
+        ].into_iter()
+    }
+    /// Style of the heading, from the level 1
+    pub fn heading(&self, level: usize) -> Style {
+        self.headings.get(level.saturating_sub(1)).or(self.headings.last()).copied().unwrap_or_default()
+    }
+}
+
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+/// Set the theme of the terminal renderer, before the first use of the theme
+pub fn init(config: &Config) {
+    let theme = Theme::from_config(config);
+    // crossterm only reads `NO_COLOR`
+    crossterm::style::force_color_output(theme.color_mode != ColorMode::Never);
+    if THEME.set(theme).is_err() {
+        log::warn!("The theme is already set");
+    }
+}
+
+/// Theme of the terminal renderer, the default one if not initialized
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| Theme::from_config(&Config::default()))
+}
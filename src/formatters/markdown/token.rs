@@ -1,6 +1,8 @@
 // use smartstring::alias::String;
+use serde::Serialize;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum InlineStyleToken {
     OneStar,
     TwoStars,
@@ -8,15 +10,22 @@ pub enum InlineStyleToken {
     OneDash,
     TwoDashes,
     OneQuote,
+    TwoQuotes,
+    TwoTildes,
+    /// Inline math, converted to unicode
+    OneDollar,
+    /// Style of the links, only used by the renderers
+    Link,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Marker {
     Begin(InlineStyleToken),
     End(InlineStyleToken),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Level(usize);
 
 impl<UInt: num_traits::Unsigned + Into<usize>> From<UInt> for Level {
@@ -31,7 +40,30 @@ impl From<Level> for usize {
 }
 
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListKind {
+    Unordered,
+    Ordered(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ListItem {
+    pub level: Level,
+    pub kind: ListKind,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    Default,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Token {
     Text(String),
     Newline,
@@ -40,6 +72,20 @@ pub enum Token {
     EndCode,
     Line,
     Heading(Level),
-    // ListItem(Level),
+    ListItem(ListItem),
+    Checkbox(bool),
+    /// Beginning of a table row, followed by its cells
+    TableRow,
+    /// Beginning of a table cell, followed by its content
+    TableCell,
+    /// Alignment of the columns, the rows before are the header of the table
+    TableAlignment(Vec<Alignment>),
+    EndTable,
+    /// One level of block quote, at the beginning of the line
+    BlockQuote,
+    /// Beginning of a link to the url, followed by the text of the link
+    BeginLink(String),
+    EndLink,
+    Image { alt: String, url: String },
     EndDocument,
 }
\ No newline at end of file
@@ -1,20 +1,65 @@
-mod markdown;
+pub mod markdown;
+pub mod json;
+pub mod pager;
 mod raw;
 
 use anyhow::Result;
 
 use raw::RawFormater;
-pub type MarkdownFormatter = markdown::Parser<markdown::TerminalRenderer>;
+use json::JsonFormatter;
+use pager::Pager;
+use crate::generators;
+pub type MarkdownFormatter<W = std::io::Stdout> = markdown::Parser<markdown::TerminalRenderer<W>>;
+pub type HtmlFormatter = markdown::Parser<markdown::HtmlRenderer>;
+pub type PlainFormatter<W = std::io::Stdout> = markdown::Parser<markdown::PlainRenderer<W>>;
 
 pub trait Formatter {
     fn push(&mut self, text: &str) -> Result<()>;
+    /// Information of the generation other than its text, ignored by default
+    fn push_event(&mut self, _event: &generators::Event) -> Result<()> {
+        Ok(())
+    }
     fn end_of_document(&mut self) -> Result<()> {
         Ok(())
     }
+    /// Interruption of the generation by the user, ends the document by default
+    fn cancel(&mut self) -> Result<()> {
+        self.end_of_document()
+    }
+}
+
+/// Reset the styles and the modes of the terminal, left applied by an interrupted output
+pub fn reset_terminal() -> std::io::Result<()> {
+    use std::io::IsTerminal;
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return Ok(());
+    }
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(stdout,
+        crossterm::style::ResetColor,
+        crossterm::style::SetAttribute(crossterm::style::Attribute::Reset),
+        crossterm::cursor::Show,
+    )
 }
 
-pub fn new_markdown_formatter() -> MarkdownFormatter {
-    markdown::Parser::new(markdown::TerminalRenderer::new())
+pub fn new_markdown_formatter(config: &markdown::config::Config) -> MarkdownFormatter {
+    markdown::Parser::new(markdown::TerminalRenderer::new(config))
+}
+pub fn new_paged_markdown_formatter(config: &markdown::config::Config, pager: Pager) -> MarkdownFormatter<Pager> {
+    markdown::Parser::new(markdown::TerminalRenderer::with_output(pager, config, markdown::terminal_width()))
+}
+pub fn new_html_formatter(config: &markdown::config::Config) -> HtmlFormatter {
+    markdown::Parser::new(markdown::HtmlRenderer::new(config))
+}
+pub fn new_plain_formatter() -> PlainFormatter {
+    markdown::Parser::new(markdown::PlainRenderer::default())
+}
+pub fn new_paged_plain_formatter(pager: Pager) -> PlainFormatter<Pager> {
+    markdown::Parser::new(markdown::PlainRenderer::with_output(pager))
+}
+pub fn new_json_formatter() -> JsonFormatter {
+    JsonFormatter::default()
 }
 pub fn new_raw_formatter() -> RawFormater {
     RawFormater
@@ -0,0 +1,264 @@
+//! Output of the formatters, paged once it doesn't fit in the terminal
+
+use std::io::{ErrorKind, Write};
+use std::process::{Child, Command, Stdio};
+use thiserror::Error;
+use unicode_width::UnicodeWidthChar;
+
+const DEFAULT_COMMAND: &str = "less";
+
+#[derive(Error, Debug)]
+pub enum PagerError {
+    #[error("invalid pager command `{0}`")]
+    InvalidCommand(String),
+}
+
+/// Lines of the output with the moves of the cursor applied
+///
+/// The terminal renderer moves the cursor inside the current line to draw it again
+/// (highlighted code, wrapped words, centered headings), which a pager doesn't interpret.
+/// The lines are only complete once their newline is written.
+///
+/// With a width, the rows taken by the lines in the terminal are counted, the lines longer
+/// than the width being wrapped by the terminal.
+#[derive(Default)]
+pub struct LineFlattener {
+    /// Escape sequences written before each character of the line, and the character
+    cells: Vec<(String, String)>,
+    column: usize,
+    /// Escape sequences written since the last character
+    escapes: String,
+    /// End of the last write, which is not a complete character or escape sequence
+    pending: Vec<u8>,
+    /// Width of the terminal, 0 if the rows are not counted
+    width: usize,
+    /// Rows taken by the ended lines
+    ended_rows: usize,
+}
+
+impl LineFlattener {
+    pub fn with_width(width: usize) -> Self {
+        Self { width, ..Default::default() }
+    }
+    /// Rows taken in the terminal by the output pushed so far
+    pub fn rows(&self) -> usize {
+        self.ended_rows + self.line_rows()
+    }
+    fn line_rows(&self) -> usize {
+        match self.width {
+            0 => 0,
+            width => self.cells.len().div_ceil(width),
+        }
+    }
+    /// Push the written bytes, returns the completed lines
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(bytes);
+        let pending = std::mem::take(&mut self.pending);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text,
+            // the last character is not complete
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&pending[..e.valid_up_to()]).unwrap_or_default(),
+            Err(_) => return self.push(String::from_utf8_lossy(&pending).as_bytes()),
+        };
+        let mut lines = String::new();
+        let consumed = self.push_str(valid, &mut lines);
+        self.pending = pending[consumed..].to_vec();
+        lines.into_bytes()
+    }
+    /// Returns the line not ended by a newline
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut line = String::from_utf8_lossy(&std::mem::take(&mut self.pending)).into_owned();
+        if !self.cells.is_empty() || !self.escapes.is_empty() {
+            self.end_line(&mut line);
+        }
+        line.into_bytes()
+    }
+    /// Returns the length of the text consumed, the rest is an incomplete escape sequence
+    fn push_str(&mut self, text: &str, lines: &mut String) -> usize {
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '\x1b' => {
+                    let Some(length) = Self::escape_length(&text[start..]) else { return start };
+                    self.push_escape(&text[start..start + length]);
+                    while chars.peek().is_some_and(|(i, _)| *i < start + length) {
+                        chars.next();
+                    }
+                }
+                '\n' => {
+                    self.end_line(lines);
+                    lines.push('\n');
+                }
+                '\r' => self.column = 0,
+                c => self.push_char(c),
+            }
+        }
+        text.len()
+    }
+    /// Length of the escape sequence at the beginning of the text, None if it is not complete
+    fn escape_length(text: &str) -> Option<usize> {
+        match text.as_bytes().get(1)? {
+            // control sequence, ended by a byte in the range `@` to `~`
+            b'[' => text.bytes().skip(2).position(|b| (0x40..=0x7e).contains(&b)).map(|i| i + 3),
+            // operating system command like the hyperlinks, ended by BEL or ST
+            b']' => {
+                let bel = text.find('\x07').map(|i| i + 1);
+                let st = text.find("\x1b\\").map(|i| i + 2);
+                bel.into_iter().chain(st).min()
+            }
+            _ => Some(2),
+        }
+    }
+    fn push_escape(&mut self, escape: &str) {
+        let parameter = || escape[2..escape.len() - 1].parse::<usize>().ok();
+        match escape.as_bytes() {
+            // move to the column, starting from 1
+            [b'\x1b', b'[', .., b'G'] => self.column = parameter().unwrap_or(1).saturating_sub(1),
+            // clear until the end of the line, the styles of the cleared characters are kept
+            [b'\x1b', b'[', b'K'] | [b'\x1b', b'[', b'0', b'K'] => {
+                for (escapes, _) in self.cells.drain(self.column.min(self.cells.len())..) {
+                    self.escapes.push_str(&escapes);
+                }
+            }
+            _ => self.escapes.push_str(escape),
+        }
+    }
+    fn push_char(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            // combining character
+            match self.column.checked_sub(1).and_then(|i| self.cells.get_mut(i)) {
+                Some((_, text)) => text.push(c),
+                None => self.escapes.push(c),
+            }
+            return;
+        }
+        if self.cells.len() < self.column + width {
+            self.cells.resize(self.column + width, (String::new(), " ".to_string()));
+        }
+        let (escapes, text) = &mut self.cells[self.column];
+        escapes.push_str(&std::mem::take(&mut self.escapes));
+        *text = c.to_string();
+        // the second column of a wide character
+        for (_, text) in &mut self.cells[self.column + 1..self.column + width] {
+            text.clear();
+        }
+        self.column += width;
+    }
+    fn end_line(&mut self, lines: &mut String) {
+        if self.width > 0 {
+            self.ended_rows += self.line_rows().max(1);
+        }
+        for (escapes, text) in self.cells.drain(..) {
+            lines.push_str(&escapes);
+            lines.push_str(&text);
+        }
+        lines.push_str(&std::mem::take(&mut self.escapes));
+        self.column = 0;
+    }
+}
+
+enum State {
+    /// Written to stdout, and kept to be written again in the pager
+    Direct { written: Vec<u8>, rows: LineFlattener },
+    Paged { child: Child, lines: LineFlattener },
+    /// The pager is quit before the end of the output, which is discarded
+    Closed,
+}
+
+/// Output written to stdout, then to the pager once it exceeds the height of the terminal
+///
+/// The first lines are displayed while the answer is streamed, the pager is started with
+/// the whole output once it doesn't fit on the screen anymore.
+pub struct Pager {
+    command: Vec<String>,
+    /// Rows displayed before the pager is started
+    max_rows: usize,
+    state: State,
+}
+
+impl Pager {
+    /// Pager from the command, or else `$PAGER`, or else `less`
+    pub fn new(command: Option<&str>) -> Result<Self, PagerError> {
+        let command = command.map(str::to_string)
+            .or_else(|| std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()))
+            .unwrap_or_else(|| DEFAULT_COMMAND.to_string());
+        let args = shlex::split(&command).filter(|args| !args.is_empty()).ok_or(PagerError::InvalidCommand(command))?;
+        let (width, max_rows) = crossterm::terminal::size()
+            .map(|(columns, rows)| (columns as usize, rows.saturating_sub(1) as usize))
+            .unwrap_or((0, usize::MAX));
+        Ok(Self {
+            command: args,
+            max_rows,
+            state: State::Direct { written: Vec::new(), rows: LineFlattener::with_width(width) },
+        })
+    }
+    fn start(&mut self) {
+        let State::Direct { written, .. } = &mut self.state else { return };
+        let mut command = Command::new(&self.command[0]);
+        command.args(&self.command[1..]).stdin(Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            // the colors are written as escape codes
+            command.env("LESS", "R");
+        }
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                log::warn!("Failed to start the pager `{}`: {}", self.command.join(" "), e);
+                self.max_rows = usize::MAX;
+                return;
+            }
+        };
+        let written = std::mem::take(written);
+        self.state = State::Paged { child, lines: LineFlattener::default() };
+        self.write_paged(&written);
+    }
+    fn write_paged(&mut self, buf: &[u8]) {
+        let State::Paged { child, lines } = &mut self.state else { return };
+        let lines = lines.push(buf);
+        let Some(stdin) = child.stdin.as_mut() else { return };
+        if let Err(e) = stdin.write_all(&lines).and_then(|_| stdin.flush()) {
+            if e.kind() != ErrorKind::BrokenPipe {
+                log::warn!("Failed to write in the pager: {}", e);
+            }
+            self.state = State::Closed;
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.state {
+            State::Direct { written, rows } => {
+                std::io::stdout().write_all(buf)?;
+                written.extend_from_slice(buf);
+                rows.push(buf);
+                if rows.rows() > self.max_rows {
+                    self.start();
+                }
+            }
+            State::Paged { .. } => self.write_paged(buf),
+            State::Closed => (),
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.state {
+            State::Direct { .. } => std::io::stdout().flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Pager {
+    /// Wait for the pager to be quit
+    fn drop(&mut self) {
+        let State::Paged { mut child, mut lines } = std::mem::replace(&mut self.state, State::Closed) else { return };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&lines.finish());
+        }
+        if let Err(e) = child.wait() {
+            log::warn!("Failed to wait for the pager: {}", e);
+        }
+    }
+}
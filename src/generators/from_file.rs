@@ -1,7 +1,7 @@
 use std::io::Cursor;
 
 use crate::args;
-use super::{ResultRun, ResultStream, Error};
+use super::{ResultRun, ResultStream, Error, Event};
 use tokio_util::io::ReaderStream;
 
 #[inline]
@@ -19,19 +19,19 @@ pub async fn run(_: crate::config::Config, args: args::FromContentArgs, input: &
         let file = tokio::fs::File::open(&input).await.map_err(err_into)?;
         let stream = ReaderStream::new(file).map(|r| -> ResultStream {
             let bytes = res_into(r)?;
-            String::from_utf8(bytes.as_ref().to_vec()).map_err(err_into)
+            String::from_utf8(bytes.as_ref().to_vec()).map(Event::Text).map_err(err_into)
         });
-        return Ok(Box::pin(stream));
+        Ok(Box::pin(stream))
     } else {
         let stream = ReaderStream::new(Cursor::new(String::from(input).into_bytes()))
             .map(res_into)
             .map(|r| 
                 r.and_then(|v| 
-                    res_into(std::str::from_utf8(v.as_ref())).map(String::from)
+                    res_into(std::str::from_utf8(v.as_ref())).map(|s| Event::Text(s.to_string()))
                 )
                 
             );
-        return Ok(Box::pin(stream));
+        Ok(Box::pin(stream))
         // todo!("Implement reading from stdin")
     }
 }
\ No newline at end of file
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use super::template::PromptTemplate;
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub models: Vec<Model>,
 }
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Model {
     pub name: String,
     pub path: String,
@@ -15,8 +18,9 @@ pub struct Model {
     pub parameters: ModelParameters,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(default)]
+#[serde(deny_unknown_fields)]
 pub struct ModelParameters {
     pub n_gpu_layers: u32,
     pub split_mode: SplitMode,
@@ -64,7 +68,7 @@ impl From<&ModelParameters> for llama_cpp::LlamaParams {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Copy, Clone)]
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SplitMode {
     None,
@@ -5,14 +5,14 @@ pub mod stop;
 use tokio_stream::StreamExt;
 
 use llama_cpp::{
-    standard_sampler::StandardSampler, LlamaModel, LlamaParams, SessionParams, TokensToStrings
+    standard_sampler::StandardSampler, LlamaModel, SessionParams, TokensToStrings
 };
 use once_cell::sync::OnceCell;
 use log::{debug, info};
 use crate::{
-    args, config::Config, utils::hashmap
+    args, config::{variables::Variables, Config}
 };
-use super::{Error, ResultRun};
+use super::{Error, Event, ResultRun};
 
 static LOCAL_LLAMA: OnceCell<LlamaModel> = OnceCell::new();
 
@@ -32,7 +32,7 @@ fn init_model(config: &config::Model) -> Result<(), Error> {
 pub async fn run(
     config: Config, 
     args: args::LocalArgs,
-    input: &str
+    variables: &Variables
 ) -> ResultRun {
     let prompt = match args.prompt {
         Some(prompt) => config.prompts.0
@@ -44,7 +44,7 @@ pub async fn run(
             .or_else(|| config.prompts.0.first())
     }
     .ok_or_else(|| Error::Custom("Prompt not found in config".into()))?;
-    let messages = prompt.formatted_messages(&hashmap!(input => input));
+    let messages = prompt.formatted_messages(variables)?;
     let model_config = config.local.models.into_iter()
         .find(|c| c.name == args.model)
         .ok_or_else(|| Error::Custom("Model not found in config".into()))?;
@@ -76,7 +76,7 @@ pub async fn run(
         .start_completing_with(StandardSampler::default(), prompt.parameters.max_tokens.unwrap_or(1024) as _)
         .map_err(|e| Error::Custom(format!("Failed to start completion: {e}").into()))?;
     if log::log_enabled!(log::Level::Trace) {
-        let completion_stream = StreamExt::map(completion,  |token| Ok(format!("{}({})", model.token_to_piece(token), token.0)));
+        let completion_stream = StreamExt::map(completion,  |token| Ok(Event::Text(format!("{}({})", model.token_to_piece(token), token.0))));
         Ok(Box::pin(completion_stream))
     } else {
         let mut discard_tokens = model_config.template.stop_tokens(model).map_err(|_| Error::Custom("Failed to convert prompt messages to tokens".into()))?;
@@ -86,7 +86,7 @@ pub async fn run(
                     TokensToStrings::new(completion, model.clone()), 
                     move |token| !discard_tokens.check(token)
                 ),
-                Ok
+                |text| Ok(Event::Text(text))
             );
         
         Ok(Box::pin(completion_stream))
@@ -23,12 +23,12 @@ impl StopToken {
     pub fn from_tokens(model: Model, tokens: &[Token]) -> Self {
         Self(model.decode_tokens(tokens),)
     }
-    pub fn inspect(&self) -> StopTokenInspector {
+    pub fn inspect(&self) -> StopTokenInspector<'_, '_> {
         StopTokenInspector::new(self)
     }
 }
 
-enum StopTokenState {
+pub enum StopTokenState {
     NotFound,
     InProgress,
     Found,
@@ -118,6 +118,7 @@ impl<'a, 'b> StopTokenInspector<'a, 'b> {
     }
 }
 
+#[derive(Default)]
 pub struct StopManager {
     stops: Vec<(StopToken, usize)>,
 }
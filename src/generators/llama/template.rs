@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use crate::{
     config::prompt::{Message, Role},
     utils::vec_merge
@@ -6,7 +7,8 @@ use crate::{
 use llama_cpp::{LlamaTokenizationError, Token};
 use super::stop::{stop_manager, StopManager};
 
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct CustomTemplate {
     pub system_prefix: String,
     pub system_suffix: String,
@@ -15,7 +17,7 @@ pub struct CustomTemplate {
     pub assistant_prefix: String,
     pub assistant_suffix: String,
 }
-#[derive(Default, Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum PromptTemplate {
     #[default]
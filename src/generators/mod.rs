@@ -5,6 +5,7 @@ pub mod from_file;
 
 use tokio_stream::Stream;
 use thiserror::Error;
+use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, pin::Pin};
 
 #[derive(Debug, Error)]
@@ -14,10 +15,30 @@ pub enum Error {
     #[error("An error occured while serializing json response: {0}")]
     SerializeJSON(#[from] serde_json::Error),
     #[error("An error ocurred: {0}")]
-    Boxed(#[from] Box<dyn std::error::Error>),
+    Boxed(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Invalid prompt: {0}")]
+    Prompt(#[from] crate::config::prompt::PromptError),
     #[error("An error ocurred: {0}")]
     Custom(Cow<'static, str>)
 }
 
-pub type ResultStream = Result<String, Error>;
+/// Number of tokens used by a request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// Item of the stream of a generator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Next part of the completion
+    Text(String),
+    Usage(Usage),
+    /// Reason why the generation stopped, as reported by the engine
+    Finish(String),
+}
+
+pub type ResultStream = Result<Event, Error>;
 pub type ResultRun = Result<Pin<Box<dyn Stream<Item = ResultStream>>>, Error>;
\ No newline at end of file
@@ -1,8 +0,0 @@
-use std::collections::HashMap;
-
-use serde::{Deserialize, Serialize};
-use super::ChatRequestParameters;
-use super::{Message, Role};
-
-
-
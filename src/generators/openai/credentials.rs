@@ -1,7 +1,9 @@
 use serde::Deserialize;
+use schemars::JsonSchema;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Credentials {
+    /// Key of the OpenAI API, `$OPENAI_API_KEY` if not set
     #[serde(default = "Credentials::api_key_from_env")]
     pub api_key: String
 }
@@ -1,16 +1,15 @@
-pub mod config;
 pub mod credentials;
 
 use serde::{Serialize, Deserialize};
 use tokio_stream::StreamExt;
 use crate::{
-    args, config::prompt::Stop, utils::{
-        hashmap, FlattenTrait, SplitBytesFactory
+    args, config::{prompt::Stop, variables::Variables}, utils::{
+        FlattenTrait, SplitBytesFactory
     }
 };
 use crate::config::prompt::{Prompt, Parameters as PromptParameters, Message, Role};
 
-use super::{ResultRun, Error};
+use super::{ResultRun, Error, Event, Usage};
 
 #[derive(Debug, Default, Serialize)]
 pub struct ChatRequestParameters {
@@ -34,6 +33,14 @@ pub struct ChatRequestParameters {
     pub echo: Option<bool>,
     #[serde(skip_serializing_if = "Stop::is_none")]
     pub stop: Stop,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct StreamOptions {
+    /// Send the usage of the request in the last chunk of the stream
+    pub include_usage: bool,
 }
 
 impl From<PromptParameters> for ChatRequestParameters {
@@ -67,7 +74,7 @@ impl ChatRequest {
         }
     }
     pub fn add_message(mut self, role: Role, content: String) -> Self {
-        self.messages.push(Message { role, content: Some(content) });
+        self.messages.push(Message { role, content: Some(content), fragment: None });
         self
     }
     pub fn add_messages(mut self, messages: Vec<Message>) -> Self {
@@ -78,8 +85,10 @@ impl ChatRequest {
         self.parameters = parameters;
         self
     }
-    pub fn into_stream(mut self) -> Self {
+    /// Stream the response, with the usage in the last chunk if requested
+    pub fn into_stream(mut self, include_usage: bool) -> Self {
         self.parameters.stream = Some(true);
+        self.parameters.stream_options = include_usage.then_some(StreamOptions { include_usage });
         self
     }
 }
@@ -103,7 +112,7 @@ struct Delta {
 struct Choice {
     pub delta: Delta,
     // pub index: u32,
-    // pub finish_reason: Option<String>,
+    pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -114,7 +123,11 @@ enum ChatResponse {
         // object: String,
         // created: u64,
         // model: String,
+        #[serde(default)]
         choices: Vec<Choice>,
+        /// Only in the last chunk, with an empty list of choices
+        #[serde(default)]
+        usage: Option<Usage>,
     },
     Status {
         status: String
@@ -149,6 +162,21 @@ impl std::fmt::Display for ChatResponse {
 }
 
 impl ChatResponse {
+    fn into_events(self) -> Vec<Event> {
+        match self {
+            ChatResponse::Message { choices, usage } => {
+                let mut events = Vec::new();
+                if let Some(choice) = choices.into_iter().next() {
+                    events.extend(choice.delta.content.map(Event::Text));
+                    events.extend(choice.finish_reason.map(Event::Finish));
+                }
+                events.extend(usage.map(Event::Usage));
+                events
+            }
+            status @ ChatResponse::Status { .. } => vec![Event::Text(status.to_string())],
+            ChatResponse::Done => Vec::new(),
+        }
+    }
     pub fn from_slice(bytes: &[u8]) -> Result<Self, serde_json::Error> {
         // eprintln!("from_bytes|1: {}", String::from_utf8_lossy(&bytes));
         if !bytes.starts_with(b"data: ") {
@@ -178,7 +206,8 @@ impl ChatResponse {
     }
 }
 
-pub async fn run(creds: credentials::Credentials, config: crate::config::Config, args: args::ApiArgs, input: &str) -> ResultRun {
+/// Stream the completion of the prompt, `include_usage` adds the usage of the request to the events
+pub async fn run(creds: credentials::Credentials, config: crate::config::Config, args: args::ApiArgs, variables: &Variables, include_usage: bool) -> ResultRun {
     let openai_api_key = creds.api_key;
 
     if openai_api_key.is_empty() {
@@ -189,16 +218,16 @@ pub async fn run(creds: credentials::Credentials, config: crate::config::Config,
         config.prompts.0.into_iter()
             .find(|prompt| prompt.name == config_prompt)
             .ok_or(Error::Custom("Prompt not found".into()))?
-            .format_contents(&hashmap!(input => input))
+            .format_contents(variables)?
     } else {
-        Prompt::from_input(input)
+        Prompt::from_input(variables.get("input").map(String::as_str).unwrap_or_default())
     };
 
     // Send a request
     let chat_request = ChatRequest::new(args.model)
         .add_messages(prompt.messages)
         .set_parameters(prompt.parameters.into())
-        .into_stream();
+        .into_stream(include_usage);
 
     let client = reqwest::Client::new();
     let stream = client.post("https://api.openai.com/v1/chat/completions")
@@ -246,10 +275,11 @@ pub async fn run(creds: credentials::Credentials, config: crate::config::Config,
         })
         .map_while(|resp| {
             match resp {
-                Ok(msg @ (ChatResponse::Message { .. } | ChatResponse::Status{ .. })) => Some(Ok(msg.to_string())),
+                Ok(msg @ (ChatResponse::Message { .. } | ChatResponse::Status{ .. })) => Some(Ok(msg.into_events().into_iter())),
                 Ok(ChatResponse::Done) => None,
                 Err(e) => Some(Err(e)),
             }
-        });
+        })
+        .flatten_result_iter();
     Ok(Box::pin(stream_string))
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+//! Streaming of the completions of AI engines, rendered as markdown in the terminal
+//!
+//! The `aio` binary is a thin wrapper of this crate: the configuration is loaded with
+//! [`config::Config`], a generator of [`generators`] streams the completion as
+//! [`generators::Event`]s, each text is pushed to a [`formatters::Formatter`] and to the
+//! [`runner::Runner`] collecting the code blocks.
+//!
+//! ```no_run
+//! use aio_cli::{args, config::Config, formatters::{self, Formatter}, generators, runner};
+//! use tokio_stream::StreamExt;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let config = Config::from_yaml_file("config.yml").map_err(anyhow::Error::msg)?;
+//! let mut formatter = formatters::new_markdown_formatter(&config.markdown);
+//! let mut code_blocks = runner::Runner::new(args::RunChoice::No, false, Default::default(), Default::default());
+//! let args = args::FromContentArgs { file: false };
+//! let mut stream = generators::from_file::run(config, args, "Hello\n```sh\necho hi\n```\n").await?;
+//! while let Some(event) = stream.next().await {
+//!     if let generators::Event::Text(text) = event? {
+//!         formatter.push(&text)?;
+//!         code_blocks.push(&text)?;
+//!     }
+//! }
+//! formatter.end_of_document()?;
+//! for code_block in code_blocks.code_blocks() {
+//!     println!("{}: {}", code_block.language(), code_block.code());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod arguments;
+pub mod config;
+pub mod credentials;
+pub mod filesystem;
+pub mod formatters;
+pub mod generators;
+pub mod runner;
+pub mod serde_io;
+#[cfg(test)]
+mod tests;
+mod utils;
+
+pub use arguments as args;
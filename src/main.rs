@@ -1,21 +1,7 @@
-pub mod arguments;
-mod config;
-mod credentials;
-mod filesystem;
-mod formatters;
-mod generators;
-mod runner;
-mod serde_io;
-#[cfg(test)]
-mod tests;
-mod utils;
-
-mod openai {}
-
-use arguments as args;
+use aio_cli::{args, config, credentials, filesystem, formatters, generators, runner};
 use clap::Parser;
 use formatters::Formatter;
-use serde_io::DeserializeExt;
+use aio_cli::serde_io::DeserializeExt;
 use tokio_stream::StreamExt;
 
 macro_rules! raise_str {
@@ -36,9 +22,22 @@ fn get_creds(creds_path: &str) -> Result<credentials::Credentials, String> {
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
+    let app_args = args::Args::parse();
+    let json_output = app_args.formatter == args::FormatterChoice::Json;
+    match run(app_args).await {
+        Err(message) if json_output => {
+            // the error is an event of the output, for the programs reading it
+            let _ = formatters::json::print_event(&formatters::json::Event::Error { message });
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(app_args: args::Args) -> Result<(), String> {
     let app_args = {
-        let mut args = args::Args::parse();
-        if args.input.is_empty() {
+        let mut args = app_args;
+        if args.input.is_empty() && !matches!(args.engine, args::Subcommands::History(_) | args::Subcommands::Prompts(_) | args::Subcommands::Config(_)) {
             use std::io::Read;
             let mut str_input = std::string::String::new();
             let mut stdin = std::io::stdin();
@@ -62,11 +61,29 @@ async fn main() -> Result<(), String> {
     simplelog::TermLogger::init(
         log_level,
         simplelog::Config::default(),
-        simplelog::TerminalMode::Stdout,
+        simplelog::TerminalMode::Stderr,
         simplelog::ColorChoice::Auto,
     )
     .unwrap();
 
+    if let args::Subcommands::Config(args::ConfigCommands::Check) = &app_args.engine {
+        let variables = raise_str!(config::variables::from_args(&app_args), "Invalid prompt variables: {}");
+        let diagnostics = config::check::check(filesystem::resolve_path(&app_args.config_path).as_ref().as_ref(), &variables);
+        for diagnostic in &diagnostics {
+            println!("{}: {}", diagnostic.severity, diagnostic);
+        }
+        let errors = diagnostics.iter().filter(|d| d.severity == config::check::Severity::Error).count();
+        if errors > 0 {
+            return Err(format!("The configuration has {} error(s)", errors));
+        }
+        println!("The configuration is valid");
+        return Ok(());
+    }
+
+    if let args::Subcommands::Config(args::ConfigCommands::Schema(args)) = &app_args.engine {
+        return config::schema::command(args).map_err(|e| format!("Failed to write the schema: {}", e));
+    }
+
     let config =
         config::Config::from_yaml_file(filesystem::resolve_path(&app_args.config_path).as_ref())
             .map_err(|e| {
@@ -76,47 +93,121 @@ async fn main() -> Result<(), String> {
                 )
             })?;
 
-    let mut formatter: Box<dyn Formatter> = match app_args.formatter {
-        args::FormatterChoice::Markdown => Box::new(formatters::new_markdown_formatter()),
-        args::FormatterChoice::Raw => Box::new(formatters::new_raw_formatter()),
+    if let args::Subcommands::History(command) = &app_args.engine {
+        return runner::history::command(command, &config.runner).map_err(|e| format!("Failed to browse history: {}", e));
+    }
+    if let args::Subcommands::Prompts(command) = &app_args.engine {
+        return config::library::command(command, &config).map_err(|e| format!("Failed to manage prompts: {}", e));
+    }
+    if let args::Subcommands::Config(command) = &app_args.engine {
+        return config::command(command, &config).map_err(|e| format!("Failed to display the configuration: {}", e));
+    }
+
+    formatters::markdown::theme::init(&config.theme);
+    let is_terminal = std::io::IsTerminal::is_terminal(&std::io::stdout());
+    let formatter_choice = match app_args.formatter {
+        args::FormatterChoice::Markdown if !is_terminal && formatters::markdown::theme::current().color_mode != formatters::markdown::theme::ColorMode::Always => {
+            log::info!("The output is not a terminal, the plain formatter is used");
+            args::FormatterChoice::Plain
+        }
+        choice => choice,
+    };
+    let pager = if is_terminal && (app_args.pager || config.markdown.pager) && !app_args.no_pager {
+        Some(raise_str!(formatters::pager::Pager::new(config.markdown.pager_command.as_deref()), "Invalid pager: {}"))
+    } else {
+        None
     };
-    let mut runner = runner::Runner::new(app_args.run);
+    let mut formatter: Box<dyn Formatter> = match (formatter_choice, pager) {
+        (args::FormatterChoice::Markdown, Some(pager)) => Box::new(formatters::new_paged_markdown_formatter(&config.markdown, pager)),
+        (args::FormatterChoice::Markdown, None) => Box::new(formatters::new_markdown_formatter(&config.markdown)),
+        (args::FormatterChoice::Plain, Some(pager)) => Box::new(formatters::new_paged_plain_formatter(pager)),
+        (args::FormatterChoice::Plain, None) => Box::new(formatters::new_plain_formatter()),
+        (args::FormatterChoice::Html, _) => Box::new(formatters::new_html_formatter(&config.markdown)),
+        (args::FormatterChoice::Json, _) => Box::new(formatters::new_json_formatter()),
+        (args::FormatterChoice::Raw, _) => Box::new(formatters::new_raw_formatter()),
+    };
+    let run_options = raise_str!(runner::RunOptions::from_args(&app_args, &config.runner), "Invalid run options: {}");
+    let mut runner = runner::Runner::new(app_args.run, app_args.chain, run_options, runner::RunContext::from_args(&app_args));
 
+    let mut variables = raise_str!(config::variables::from_args(&app_args), "Invalid prompt variables: {}");
+    config::variables::add_env(&mut variables, &config.variables);
     let mut stream = match app_args.engine {
         args::Subcommands::Api(args_engine) => generators::openai::run(
             get_creds(&app_args.creds_path)?.openai,
             config,
             args_engine,
-            &app_args.input,
+            &variables,
+            // only the json formatter outputs the usage
+            formatter_choice == args::FormatterChoice::Json,
         )
         .await
         .map_err(|e| format!("Failed to request OpenAI API: {}", e))?,
+        #[cfg(feature = "local-llm")]
         args::Subcommands::Local(args_engine) => {
-            generators::llama::run(config, args_engine, &app_args.input)
+            generators::llama::run(config, args_engine, &variables)
                 .await
                 .map_err(|e| format!("Unable to run local model: {}", e))?
         }
+        #[cfg(not(feature = "local-llm"))]
+        args::Subcommands::Local(_) => return Err("aio is built without the `local-llm` feature".to_string()),
         args::Subcommands::FromContent(args_engine) => {
             generators::from_file::run(config, args_engine, &app_args.input)
                 .await
                 .map_err(|e| format!("Failed to read from file: {}", e))?
         }
+        args::Subcommands::History(_) => unreachable!("History subcommand is handled before"),
+        args::Subcommands::Prompts(_) => unreachable!("Prompts subcommand is handled before"),
+        args::Subcommands::Config(_) => unreachable!("Config subcommand is handled before"),
     };
 
+    // Ctrl-C cancels the generation, the code blocks already complete can still be run
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut cancelled = false;
     loop {
-        match stream.next().await {
-            Some(Ok(token)) => {
+        let event = tokio::select! {
+            event = stream.next() => event,
+            _ = &mut ctrl_c => {
+                cancelled = true;
+                break;
+            }
+        };
+        match event {
+            Some(Ok(generators::Event::Text(token))) => {
                 raise_str!(formatter.push(&token), "Failed to parse markdown: {}");
                 raise_str!(
                     runner.push(&token),
                     "Failed push text in the runner system: {}"
                 );
             }
+            Some(Ok(event)) => raise_str!(formatter.push_event(&event), "Failed to format the generation: {}"),
             Some(Err(e)) => Err(e.to_string())?,
             None => break,
         }
     }
-    raise_str!(formatter.end_of_document(), "Failed to end markdown: {}");
+    drop(stream);
+    if cancelled {
+        raise_str!(formatter.cancel(), "Failed to end markdown: {}");
+    } else {
+        raise_str!(formatter.end_of_document(), "Failed to end markdown: {}");
+    }
+    // waits for the pager to be quit before running the code blocks
+    drop(formatter);
+    if cancelled {
+        raise_str!(formatters::reset_terminal(), "Failed to reset the terminal: {}");
+        eprintln!("[cancelled]");
+    }
+    // the default handler of Ctrl-C is replaced, exit on the next one
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let _ = formatters::reset_terminal();
+            std::process::exit(130);
+        }
+    });
+    if cancelled {
+        raise_str!(runner.cancel(), "Failed to run code: {}");
+        std::process::exit(130);
+    }
     raise_str!(runner.end_of_document(), "Failed to run code: {}");
     Ok(())
 }
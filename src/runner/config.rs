@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub python: PythonConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct PythonConfig {
+    /// Run the scripts with inline dependencies with `uv` when it is installed
+    pub uv: bool,
+    /// Run the scripts without inline dependencies with the `.venv` of the working directory, if any
+    pub project_venv: bool,
+    /// Directory of wheels used to install the dependencies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub find_links: Option<String>,
+    /// Index (or local mirror) used to install the dependencies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_url: Option<String>,
+}
+
+impl Default for PythonConfig {
+    fn default() -> Self {
+        Self {
+            uv: false,
+            project_venv: true,
+            find_links: None,
+            index_url: None,
+        }
+    }
+}
+
+impl PythonConfig {
+    /// Arguments of `pip install` or `uv run` to select where the dependencies come from
+    pub fn index_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(find_links) = &self.find_links {
+            args.push("--find-links".to_string());
+            args.push(crate::filesystem::resolve_path(find_links).into_owned());
+        }
+        match &self.index_url {
+            Some(index_url) => {
+                args.push("--index-url".to_string());
+                args.push(index_url.clone());
+            }
+            None if self.find_links.is_some() => args.push("--no-index".to_string()),
+            None => (),
+        }
+        args
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{BufRead, Write};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{args, filesystem, utils};
+use super::{program, CodeBlock, RunContext, RunOptions};
+use super::config::Config as RunnerConfig;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("error while accessing to the history file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("error while parsing or encoding the history file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("no run found with id {0}")]
+    NotFound(usize),
+    #[error("{0}")]
+    Run(#[from] program::RunError),
+}
+
+/// One line of the execution log
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunRecord {
+    pub timestamp: chrono::DateTime<chrono::Local>,
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+    pub input: String,
+    pub language: String,
+    pub interpreter: String,
+    pub code_hash: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub confirmation: args::RunChoice,
+}
+
+impl RunRecord {
+    pub fn new(context: &RunContext, code_block: &CodeBlock, options: &RunOptions, execution: &program::Execution, confirmation: args::RunChoice) -> Self {
+        Self {
+            timestamp: chrono::Local::now(),
+            model: context.model.clone(),
+            prompt: context.prompt.clone(),
+            input: context.input.clone(),
+            language: code_block.language.clone(),
+            interpreter: execution.interpreter.clone(),
+            code_hash: utils::sha256_hex(&code_block.code),
+            code: code_block.code.clone(),
+            args: options.args.clone(),
+            stdin: options.stdin.clone(),
+            cwd: options.cwd.clone(),
+            exit_code: execution.output.status.code(),
+            duration_ms: execution.duration.as_millis() as _,
+            confirmation,
+        }
+    }
+    fn context(&self) -> RunContext {
+        RunContext {
+            model: self.model.clone(),
+            prompt: self.prompt.clone(),
+            input: self.input.clone(),
+        }
+    }
+    fn code_block(&self) -> CodeBlock {
+        CodeBlock {
+            code: self.code.clone(),
+            language: self.language.clone(),
+        }
+    }
+    fn options(&self, config: &RunnerConfig) -> RunOptions {
+        RunOptions {
+            args: self.args.clone(),
+            stdin: self.stdin.clone(),
+            cwd: self.cwd.clone(),
+            python: config.python.clone(),
+            capture_output: false,
+        }
+    }
+    fn exit_code_str(&self) -> String {
+        self.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".into())
+    }
+}
+
+fn history_path() -> std::path::PathBuf {
+    std::path::Path::new(filesystem::cache_dir()).join("runs.jsonl")
+}
+
+pub fn append(record: &RunRecord) -> Result<(), HistoryError> {
+    append_to(&history_path(), record)
+}
+
+/// Append the record as a json line of the file
+pub fn append_to(file_path: &std::path::Path, record: &RunRecord) -> Result<(), HistoryError> {
+    let mut file = std::fs::File::options()
+        .create(true)
+        .append(true)
+        .open(file_path)?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+pub fn load() -> Result<Vec<RunRecord>, HistoryError> {
+    load_from(&history_path())
+}
+
+/// Records of the file, the malformed lines are skipped
+pub fn load_from(file_path: &std::path::Path) -> Result<Vec<RunRecord>, HistoryError> {
+    if !file_path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = std::io::BufReader::new(std::fs::File::open(file_path)?);
+    let mut records = Vec::new();
+    for (index, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => log::warn!("Skipping malformed entry at line {} of the history: {}", index + 1, e),
+        }
+    }
+    Ok(records)
+}
+
+/// Append the record, a failure is only logged since the code has already been executed
+pub fn append_or_warn(record: &RunRecord) {
+    if let Err(e) = append(record) {
+        log::warn!("Failed to log the execution in the history: {}", e);
+    }
+}
+
+pub fn command(command: &args::HistoryCommands, config: &RunnerConfig) -> Result<(), HistoryError> {
+    match command {
+        args::HistoryCommands::Runs(args) => runs(args, config),
+    }
+}
+
+fn runs(args: &args::HistoryRunsArgs, config: &RunnerConfig) -> Result<(), HistoryError> {
+    let records = load()?;
+    let get = |id: usize| id.checked_sub(1)
+        .and_then(|i| records.get(i))
+        .ok_or(HistoryError::NotFound(id));
+    if let Some(id) = args.rerun {
+        let confirmation = if args.yes { args::RunChoice::Force } else { args::RunChoice::Ask };
+        return rerun(get(id)?, config, confirmation);
+    }
+    if let Some(id) = args.show {
+        print_record(id, get(id)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!("No code has been executed yet");
+        return Ok(());
+    }
+    let skip = records.len().saturating_sub(args.last);
+    for (i, record) in records.iter().enumerate().skip(skip) {
+        let first_line = record.code.lines().next().unwrap_or_default();
+        println!("{:>4}  {}  {:<8} exit: {:<4} {}",
+            i + 1,
+            record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            record.language,
+            record.exit_code_str(),
+            first_line
+        );
+    }
+    Ok(())
+}
+
+fn print_record(id: usize, record: &RunRecord) {
+    println!("Run #{}", id);
+    println!("Date: {}", record.timestamp.to_rfc3339());
+    println!("Model: {}", record.model.as_deref().unwrap_or("-"));
+    println!("Prompt: {}", record.prompt.as_deref().unwrap_or("-"));
+    println!("Input: {}", record.input);
+    println!("Language: {}", record.language);
+    println!("Interpreter: {}", record.interpreter);
+    if !record.args.is_empty() {
+        println!("Arguments: {}", record.args.join(" "));
+    }
+    if let Some(stdin) = &record.stdin {
+        println!("Standard input: {}", stdin);
+    }
+    if let Some(cwd) = &record.cwd {
+        println!("Working directory: {}", cwd);
+    }
+    println!("Exit code: {}", record.exit_code_str());
+    println!("Duration: {} ms", record.duration_ms);
+    println!("Confirmation: {:?}", record.confirmation);
+    println!("Code hash: {}", record.code_hash);
+    println!("Code:\n{}", record.code);
+}
+
+/// Execute the code of the record again, the confirmation is logged with the new execution
+fn rerun(record: &RunRecord, config: &RunnerConfig, confirmation: args::RunChoice) -> Result<(), HistoryError> {
+    println!("{}", record.code);
+    if confirmation == args::RunChoice::Ask {
+        print!("Execute this {} code again ? [y/N] ", record.language);
+        std::io::stdout().flush()?;
+        let mut stdin_buf = String::new();
+        std::io::stdin().read_line(&mut stdin_buf)?;
+        if !matches!(stdin_buf.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+    println!();
+    let code_block = record.code_block();
+    let options = record.options(config);
+    let execution = program::run(&code_block, &options)?;
+    append_or_warn(&RunRecord::new(&record.context(), &code_block, &options, &execution, confirmation));
+    Ok(())
+}
@@ -1,7 +1,11 @@
-mod program;
+pub(crate) mod program;
+pub mod config;
+pub mod history;
+pub mod selection;
 use crate::args;
+pub use program::RunOptions;
 use anyhow::Result;
-use super::Formatter;
+use crate::formatters::Formatter;
 
 #[derive(Default, Debug)]
 pub struct CodeBlock {
@@ -13,10 +17,42 @@ impl CodeBlock {
     fn new(language: String) -> Self {
         Self { code: String::new(), language }
     }
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+/// Origin of the code blocks, recorded in the execution log
+#[derive(Default, Debug, Clone)]
+pub struct RunContext {
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+    pub input: String,
+}
+
+impl RunContext {
+    pub fn from_args(args: &args::Args) -> Self {
+        let (model, prompt) = match &args.engine {
+            args::Subcommands::Api(args_engine) => (Some(args_engine.model.clone()), args_engine.prompt.clone()),
+            args::Subcommands::Local(args_engine) => (Some(args_engine.model.clone()), args_engine.prompt.clone()),
+            _ => (None, None),
+        };
+        Self {
+            model,
+            prompt,
+            input: args.input.clone(),
+        }
+    }
 }
+
 #[derive(Default, Debug)]
 pub struct Runner{
     interactive_mode: args::RunChoice,
+    chained: bool,
+    options: RunOptions,
+    context: RunContext,
     is_code: bool,
     is_newline: bool,
     current_token: String,
@@ -27,26 +63,30 @@ impl Formatter for Runner {
     fn push(&mut self, text: &str) -> Result<()> {
         for c in text.chars() {
             match c {
-                '`' => {
-                    if self.is_newline { 
-                        self.current_token.push(c);
-                    }
+                '`' if self.is_newline => {
+                    self.current_token.push(c);
                 },
                 '\n' => {
                     if self.current_token.starts_with("```") {
                         self.switch_code_block();
                     } else if self.is_code {
-                        self.codes.last_mut().unwrap().code.push(c);
+                        let code = &mut self.codes.last_mut().unwrap().code;
+                        code.push_str(&self.current_token);
+                        code.push(c);
                     }
                     self.current_token.clear();
                     self.is_newline = true;
                 },
                 _ => {
-                    if self.is_code {
-                        self.codes.last_mut().unwrap().code.push(c);
-                    } else if self.is_newline && self.current_token.starts_with("```") {
+                    if self.is_newline && self.current_token.starts_with("```") {
                         self.current_token.push(c);
                     } else {
+                        if self.is_code {
+                            let code = &mut self.codes.last_mut().unwrap().code;
+                            code.push_str(&self.current_token);
+                            code.push(c);
+                        }
+                        self.current_token.clear();
                         self.is_newline = false;
                     }
                 },
@@ -56,32 +96,85 @@ impl Formatter for Runner {
     }
     fn end_of_document(&mut self) -> Result<()> {
         use std::io::IsTerminal;
-        if !std::io::stdout().is_terminal() {
+        if !std::io::stdout().is_terminal() && !self.options.capture_output {
             // No code execution allowed if not in a terminal
             return Ok(())
         }
         match self.interactive_mode {
             args::RunChoice::No => return Ok(()),
+            args::RunChoice::Ask if self.options.capture_output => {
+                log::warn!("Code blocks are only run with `--run force` by the json formatter");
+            }
             args::RunChoice::Ask => self.interactive_interface()?,
             args::RunChoice::Force => {
-                for code_block in self.codes.iter() {
-                    program::run(code_block)?;
-                }
+                let selection = (1..=self.codes.len()).collect::<Vec<_>>();
+                self.execute_selection(&selection, self.chained, &self.options)?;
             },
         }
         
         Ok(())
     }
+    fn cancel(&mut self) -> Result<()> {
+        // only the complete code blocks can be run
+        if self.is_code {
+            self.codes.pop();
+            self.is_code = false;
+        }
+        self.end_of_document()
+    }
 }
 
 impl Runner {
-    pub fn new(run_choice: args::RunChoice) -> Self {
+    pub fn new(run_choice: args::RunChoice, chained: bool, options: RunOptions, context: RunContext) -> Self {
         Self  {
             is_newline: true,
             interactive_mode: run_choice,
+            chained,
+            options,
+            context,
             .. Default::default()
         }
     }
+    /// Code blocks of the text pushed so far, the last one may not be complete
+    pub fn code_blocks(&self) -> &[CodeBlock] {
+        &self.codes
+    }
+    fn execute(&self, code_block: &CodeBlock, options: &RunOptions) -> Result<()> {
+        let execution = program::run(code_block, options)?;
+        history::append_or_warn(&history::RunRecord::new(&self.context, code_block, options, &execution, self.interactive_mode));
+        if options.capture_output {
+            crate::formatters::json::print_event(&crate::formatters::json::Event::Run {
+                language: code_block.language.clone(),
+                interpreter: execution.interpreter,
+                exit_code: execution.output.status.code(),
+                duration_ms: execution.duration.as_millis() as u64,
+                stdout: String::from_utf8_lossy(&execution.output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&execution.output.stderr).into_owned(),
+            })?;
+        }
+        Ok(())
+    }
+    /// Execute the code blocks from their indices (starting from 1)
+    fn execute_selection(&self, selection: &[usize], chained: bool, options: &RunOptions) -> Result<()> {
+        let labeled_blocks = selection.iter()
+            .map(|&index| {
+                let code_block = &self.codes[index-1];
+                (Self::label(index, code_block), code_block)
+            });
+        if chained {
+            return self.execute(&program::chain(labeled_blocks)?, options);
+        }
+        for (label, code_block) in labeled_blocks {
+            if !options.capture_output {
+                println!("{}", label);
+            }
+            self.execute(code_block, options)?;
+        }
+        Ok(())
+    }
+    fn label(index: usize, code_block: &CodeBlock) -> String {
+        format!("── [{}] {} ──", index, code_block.language)
+    }
     fn switch_code_block(&mut self) {
         self.is_code = !self.is_code;
         if self.is_code {
@@ -102,8 +195,11 @@ impl Runner {
             if self.codes.len() == 1 {
                 println!("1: index of the code block");
             } else {
-                println!("1-{}: index of the code block", self.codes.len());
+                println!("1-{}: index of the code block (ex: 2, 1-3, 1,4)", self.codes.len());
+                println!("a: all the code blocks");
             }
+            println!("c <selection>: run the selection in a single shell process");
+            println!("<selection> -- <arguments>: pass arguments to the code");
             println!("q: quit");
             print!("> ");
             std::io::stdout().flush()?;
@@ -113,19 +209,31 @@ impl Runner {
             if stdin_buf == "q" {
                 return Ok(());
             }
-            let index = match stdin_buf.parse::<isize>() {
-                Ok(i) => i,
-                Err(_) => {
-                    println!("Not a number");
+            let (stdin_buf, options) = match stdin_buf.split_once("--") {
+                Some((selection, arguments)) => match program::split_args(arguments) {
+                    Ok(args) => (selection.trim(), RunOptions { args, ..self.options.clone() }),
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                },
+                None => (stdin_buf, self.options.clone()),
+            };
+            let (chained, stdin_buf) = match stdin_buf.strip_prefix('c') {
+                Some(rest) => (true, rest),
+                None => (self.chained, stdin_buf),
+            };
+            let selection = match selection::parse_selection(stdin_buf, self.codes.len()) {
+                Ok(selection) => selection,
+                Err(e) => {
+                    println!("Invalid selection: {}", e);
                     continue;
                 }
             };
-            if !(1..=self.codes.len() as isize).contains(&index) {
-                println!("Index out of range");
-                continue;
-            }
             println!();
-            program::run(&self.codes[index as usize-1])?;
+            if let Err(e) = self.execute_selection(&selection, chained, &options) {
+                println!("{}", e);
+            }
             println!();
         }
     }
@@ -29,10 +29,7 @@ impl Cache {
         if !file_path.exists() {
             return Ok(Default::default());
         }
-        let cache_file = match std::fs::File::open(file_path) {
-            Ok(file) => file,
-            Err(e) => return Err(e.into()),
-        };
+        let cache_file = std::fs::File::open(file_path)?;
         match serde_yaml::from_reader(cache_file) {
             Ok(cache) => Ok(cache),
             Err(e) => Err(e.into()),
@@ -2,17 +2,86 @@ mod cache;
 
 mod shell;
 use shell::*;
-mod rust;
+pub(crate) mod rust;
 use rust::*;
-mod python;
+pub(crate) mod python;
 use python::*;
 
 use std::borrow::Cow;
 use thiserror::Error;
 use super::CodeBlock;
+use super::config::{Config as RunnerConfig, PythonConfig};
+use crate::{args, filesystem};
 
 trait Program {
-    fn run(&self, code_block: &CodeBlock) -> Result<std::process::Output, std::io::Error>;
+    fn run(&mut self, code_block: &CodeBlock, options: &RunOptions) -> Result<std::process::Output, std::io::Error>;
+    /// Path of the program which ran the last code (ex: the python of a virtual environment, `uv` or `cargo`)
+    fn interpreter(&self) -> &str;
+}
+
+/// Options of the process running the code
+#[derive(Default, Debug, Clone)]
+pub struct RunOptions {
+    /// Arguments passed to the script
+    pub args: Vec<String>,
+    /// File forwarded to the standard input of the script
+    pub stdin: Option<String>,
+    /// Working directory of the script
+    pub cwd: Option<String>,
+    pub python: PythonConfig,
+    /// Capture the output of the process for the json events, instead of writing it to stdout
+    pub capture_output: bool,
+}
+
+impl RunOptions {
+    pub fn from_args(args: &args::Args, config: &RunnerConfig) -> Result<Self, RunError> {
+        Ok(Self {
+            args: match &args.run_args {
+                Some(run_args) => split_args(run_args)?,
+                None => Vec::new(),
+            },
+            stdin: args.run_stdin.as_deref().map(|p| filesystem::resolve_path(p).into_owned()),
+            cwd: args.run_cwd.as_deref().map(|p| filesystem::resolve_path(p).into_owned()),
+            python: config.python.clone(),
+            capture_output: args.formatter == args::FormatterChoice::Json,
+        })
+    }
+    pub(crate) fn apply(&self, command: &mut std::process::Command) -> Result<(), std::io::Error> {
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        if let Some(stdin) = &self.stdin {
+            command.stdin(std::fs::File::open(stdin)?);
+        }
+        if self.capture_output {
+            command.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped());
+        }
+        Ok(())
+    }
+}
+
+/// Split the arguments like a POSIX shell does
+pub fn split_args(args: &str) -> Result<Vec<String>, RunError> {
+    shlex::split(args).ok_or_else(|| RunError::InvalidArguments(args.to_string()))
+}
+
+/// Write the code in a temporary file, deleted when the returned path is dropped
+fn write_script(code: &str, suffix: &str) -> Result<tempfile::TempPath, std::io::Error> {
+    use std::io::Write;
+    let mut file = tempfile::Builder::new()
+        .prefix("aio_")
+        .suffix(suffix)
+        .tempfile()?;
+    file.write_all(code.as_bytes())?;
+    Ok(file.into_temp_path())
+}
+
+/// Result of a code block execution
+pub struct Execution {
+    /// Path of the program which ran the code
+    pub interpreter: String,
+    pub output: std::process::Output,
+    pub duration: std::time::Duration,
 }
 
 #[derive(Error, Debug)]
@@ -23,6 +92,12 @@ pub enum RunError {
     ProgramNotFound(String),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("invalid arguments: {0}")]
+    InvalidArguments(String),
+    #[error("chained mode only supports shell code blocks, `{0}` is not a shell language")]
+    NotChainable(String),
+    #[error("chained mode only supports code blocks of the same shell, `{0}` can't be chained with `{1}`")]
+    MixedShells(String, String),
 }
 #[derive(Error, Debug)]
 pub enum SearchError {
@@ -83,6 +158,35 @@ fn search_program(program: &str) -> Result<Option<String>, SearchError> {
     Ok(Some(found))
 }
 
+/// Family of the shell language, the languages of a family run with the same shells
+fn shell_family(language: &str) -> Option<&'static str> {
+    match language {
+        "sh" | "shell" | "bash" | "zsh" => Some("sh"),
+        "nu" => Some("nu"),
+        "pwsh" | "powershell" => Some("pwsh"),
+        _ => None,
+    }
+}
+
+/// Merge shell code blocks into a single script, so they run in the same shell process
+///
+/// Each block is preceded by a label echoed by the shell itself. The blocks must be written
+/// for the same shell, `sh`, `bash` and `zsh` being the same one.
+pub fn chain<'a>(code_blocks: impl IntoIterator<Item = (String, &'a CodeBlock)>) -> Result<CodeBlock, RunError> {
+    let mut chained: Option<CodeBlock> = None;
+    for (label, code_block) in code_blocks {
+        let Some(family) = shell_family(&code_block.language) else {
+            return Err(RunError::NotChainable(code_block.language.clone()));
+        };
+        let chained = chained.get_or_insert_with(|| CodeBlock::new(code_block.language.clone()));
+        if shell_family(&chained.language) != Some(family) {
+            return Err(RunError::MixedShells(code_block.language.clone(), chained.language.clone()));
+        }
+        chained.code.push_str(&format!("echo '{}'\n{}\n", label.replace('\'', ""), code_block.code));
+    }
+    Ok(chained.unwrap_or_default())
+}
+
 fn get_program(language: &str) -> SearchStatus {
     match language {
         "sh" | "shell" => ShellProgram::search(&["zsh", "bash", "sh"]),
@@ -97,12 +201,18 @@ fn get_program(language: &str) -> SearchStatus {
 }
 
 
-pub fn run(code_block: &CodeBlock) -> Result<std::process::Output, RunError> {
-    let program = match get_program(code_block.language.as_str()) {
+pub fn run(code_block: &CodeBlock, options: &RunOptions) -> Result<Execution, RunError> {
+    let mut program = match get_program(code_block.language.as_str()) {
         SearchStatus::Found(found) => found,
         SearchStatus::NotFound => return Err(RunError::ProgramNotFound(code_block.language.clone())),
         SearchStatus::Error(e) => return Err(RunError::Search(e)),
     };
-    Ok(program.run(code_block)?)
+    let start = std::time::Instant::now();
+    let output = program.run(code_block, options)?;
+    Ok(Execution {
+        interpreter: program.interpreter().to_string(),
+        output,
+        duration: start.elapsed(),
+    })
 }
 
@@ -1,26 +1,95 @@
 use super::*;
-pub struct PythonProgram(String);
-    
+pub struct PythonProgram {
+    python: String,
+    /// Program which ran the last code
+    interpreter: String,
+}
+
+/// Inline script metadata (PEP 723)
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ScriptMetadata {
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+impl ScriptMetadata {
+    /// Parse the `# /// script` block of the code, if any
+    pub(crate) fn parse(code: &str) -> Result<Option<Self>, std::io::Error> {
+        let mut lines = code.lines();
+        if !lines.any(|l| l.trim_end() == "# /// script") {
+            return Ok(None);
+        }
+        let mut content = String::new();
+        for line in lines {
+            if line.trim_end() == "# ///" {
+                return toml::from_str(&content)
+                    .map(Some)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid script metadata: {}", e)));
+            }
+            let line = line.strip_prefix('#').unwrap_or(line);
+            content.push_str(line.strip_prefix(' ').unwrap_or(line));
+            content.push('\n');
+        }
+        Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unclosed script metadata block"))
+    }
+}
+
+/// File written in a virtual environment once its dependencies are installed
+const COMPLETE_MARKER: &str = ".aio-complete";
+
+enum VirtualEnv {
+    /// Path of the python of the virtual environment
+    Ready(String),
+    /// Output of the command which failed to create the virtual environment
+    Failed(std::process::Output),
+}
+
 impl Program for PythonProgram {
-    fn run(&self, code_block: &CodeBlock) -> Result<std::process::Output, std::io::Error> {
-        use std::io::Write;
-        let mut process = std::process::Command::new(&self.0);
+    fn run(&mut self, code_block: &CodeBlock, options: &RunOptions) -> Result<std::process::Output, std::io::Error> {
+        let script = write_script(&code_block.code, ".py")?;
+        let dependencies = ScriptMetadata::parse(&code_block.code)?
+            .map(|m| m.dependencies)
+            .unwrap_or_default();
+        self.interpreter = self.python.clone();
+        let python = if dependencies.is_empty() {
+            Self::project_venv(options).unwrap_or_else(|| self.python.clone())
+        } else {
+            if options.python.uv {
+                if let Ok(Some(uv)) = search_program("uv") {
+                    self.interpreter = uv;
+                    return Self::run_uv(&self.interpreter, &script, options);
+                }
+                log::warn!("uv not found, fallback to a virtual environment");
+            }
+            match self.virtual_env(&dependencies, &options.python)? {
+                VirtualEnv::Ready(python) => python,
+                VirtualEnv::Failed(output) => return Ok(output),
+            }
+        };
+        self.interpreter = python.clone();
+        let mut process = std::process::Command::new(python);
         process
-            .arg("-")
-            .stdin(std::process::Stdio::piped());
-        let mut child = process.spawn()?;
-        child.stdin.take().expect("Failed to get stdin of python").write_all(code_block.code.as_bytes())?;
-        child.wait_with_output()
+            .arg(&script)
+            .args(&options.args);
+        options.apply(&mut process)?;
+        process.spawn()?.wait_with_output()
+    }
+    fn interpreter(&self) -> &str {
+        &self.interpreter
     }
 }
 impl PythonProgram {
+    fn new(python: String) -> Self {
+        Self { interpreter: python.clone(), python }
+    }
     pub(super) fn search() -> SearchStatus {
         if let Ok(Some(found)) = search_program("python3") {
-            return SearchStatus::Found(Box::new(Self(found)));
+            return SearchStatus::Found(Box::new(Self::new(found)));
         }
         if let Ok(Some(found)) = search_program("python") {
             if let Ok(true) = Self::check_python_version(&found) {
-                return SearchStatus::Found(Box::new(Self(found)));
+                return SearchStatus::Found(Box::new(Self::new(found)));
             }
         }
         SearchStatus::NotFound
@@ -38,4 +107,82 @@ impl PythonProgram {
         let Ok(major) = capture_version[1].parse::<u8>() else { return Ok(false); };
         Ok(major >= 3)
     }
-}
\ No newline at end of file
+    fn venv_python(venv: &std::path::Path) -> std::path::PathBuf {
+        #[cfg(target_family = "windows")]
+        return venv.join("Scripts").join("python.exe");
+        #[cfg(not(target_family = "windows"))]
+        return venv.join("bin").join("python");
+    }
+    /// Python of the `.venv` directory in the working directory
+    fn project_venv(options: &RunOptions) -> Option<String> {
+        if !options.python.project_venv {
+            return None;
+        }
+        let cwd = match &options.cwd {
+            Some(cwd) => std::path::PathBuf::from(cwd),
+            None => std::env::current_dir().ok()?,
+        };
+        let python = Self::venv_python(&cwd.join(".venv"));
+        if !python.exists() {
+            return None;
+        }
+        python.to_str().map(String::from)
+    }
+    fn run_uv(uv: &str, script: &std::path::Path, options: &RunOptions) -> Result<std::process::Output, std::io::Error> {
+        let mut process = std::process::Command::new(uv);
+        process
+            .args(["run", "--quiet", "--no-project"])
+            .args(options.python.index_args())
+            .arg("--script")
+            .arg(script)
+            .args(&options.args);
+        options.apply(&mut process)?;
+        process.spawn()?.wait_with_output()
+    }
+    /// Directory of the virtual environment of the dependencies, the same for any order of the dependencies
+    pub(crate) fn venv_dir(cache_dir: &std::path::Path, dependencies: &[String]) -> std::path::PathBuf {
+        let mut dependencies = dependencies.to_vec();
+        dependencies.sort();
+        cache_dir
+            .join("python")
+            .join(&crate::utils::sha256_hex(dependencies.join("\n"))[..16])
+    }
+    /// Whether the installation of the dependencies in the virtual environment has completed
+    pub(crate) fn is_complete(venv: &std::path::Path) -> bool {
+        venv.join(COMPLETE_MARKER).exists()
+    }
+    /// Get the python of the virtual environment of the dependencies, creating it if needed
+    ///
+    /// The virtual environments are cached by set of dependencies.
+    fn virtual_env(&self, dependencies: &[String], config: &PythonConfig) -> Result<VirtualEnv, std::io::Error> {
+        let mut dependencies = dependencies.to_vec();
+        dependencies.sort();
+        let venv = Self::venv_dir(std::path::Path::new(filesystem::cache_dir()), &dependencies);
+        let python = Self::venv_python(&venv);
+        let python_str = python.to_str().ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "bad utf-8 encoding in path"))?.to_string();
+        if Self::is_complete(&venv) {
+            return Ok(VirtualEnv::Ready(python_str));
+        }
+
+        let creation = std::process::Command::new(&self.python)
+            .args(["-m", "venv"])
+            .arg(&venv)
+            .spawn()?
+            .wait_with_output()?;
+        if !creation.status.success() {
+            return Ok(VirtualEnv::Failed(creation));
+        }
+        let installation = std::process::Command::new(&python)
+            .args(["-m", "pip", "install", "--quiet", "--disable-pip-version-check", "--no-input"])
+            .args(config.index_args())
+            .args(&dependencies)
+            .spawn()?
+            .wait_with_output()?;
+        if !installation.status.success() {
+            std::fs::remove_dir_all(&venv)?;
+            return Ok(VirtualEnv::Failed(installation));
+        }
+        std::fs::write(venv.join(COMPLETE_MARKER), dependencies.join("\n"))?;
+        Ok(VirtualEnv::Ready(python_str))
+    }
+}
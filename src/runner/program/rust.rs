@@ -1,37 +1,196 @@
 use super::*;
-pub struct RustProgram(String);
-    
+pub struct RustProgram {
+    rustc: String,
+    /// Program which built the last code
+    interpreter: String,
+}
+
+/// Crates available without dependency
+const BUILTIN_CRATES: [&str; 7] = ["std", "core", "alloc", "crate", "self", "super", "proc_macro"];
+/// Features enabled when the crate is detected from `use` statements
+const DEFAULT_FEATURES: [(&str, &[&str]); 3] = [
+    ("serde", &["derive"]),
+    ("tokio", &["full"]),
+    ("clap", &["derive"]),
+];
+
 impl Program for RustProgram {
-    fn run(&self, code_block: &CodeBlock) -> Result<std::process::Output, std::io::Error> {
-        use std::io::Write;
+    fn run(&mut self, code_block: &CodeBlock, options: &RunOptions) -> Result<std::process::Output, std::io::Error> {
+        if let Some(manifest) = Self::manifest(&code_block.code) {
+            let Ok(Some(cargo)) = search_program("cargo") else {
+                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "cargo is required to build rust code with dependencies"));
+            };
+            self.interpreter = cargo;
+            return Self::run_cargo(&self.interpreter, &manifest, code_block, options);
+        }
+        self.interpreter = self.rustc.clone();
+        let source = write_script(&code_block.code, ".rs")?;
         let tmp = tempfile::NamedTempFile::new()?.into_temp_path();
-        let tmp_path = tmp.to_str().expect("Failed to convert temp path to string");
 
-        let mut rustc_process = std::process::Command::new(&self.0);
+        let mut rustc_process = std::process::Command::new(&self.rustc);
         rustc_process
-            .args([
-                "-o",
-                tmp_path,
-                "-",
-            ])
-            .stdin(std::process::Stdio::piped());
-        let mut child = rustc_process.spawn()?;
-        child.stdin.take().expect("Failed to get stdin of rustc").write_all(code_block.code.as_bytes())?;
-        child.wait_with_output()?;
-
-        let output = std::process::Command::new(tmp_path)
-            .spawn()?
-            .wait_with_output()?;
+            .arg("-o")
+            .arg(&tmp)
+            .arg(&source);
+        let compilation = rustc_process.spawn()?.wait_with_output()?;
+        if !compilation.status.success() {
+            return Ok(compilation);
+        }
 
-        Ok(output)
+        let mut process = std::process::Command::new(&tmp);
+        process.args(&options.args);
+        options.apply(&mut process)?;
+        process.spawn()?.wait_with_output()
+    }
+    fn interpreter(&self) -> &str {
+        &self.interpreter
     }
 }
 impl RustProgram {
     pub(super) fn search() -> SearchStatus {
         match search_program("rustc") {
-            Ok(Some(found)) => SearchStatus::Found(Box::new(Self(found))),
+            Ok(Some(found)) => SearchStatus::Found(Box::new(Self { interpreter: found.clone(), rustc: found })),
             Err(e) => SearchStatus::Error(e),
             _ => SearchStatus::NotFound
         }
     }
-}
\ No newline at end of file
+    /// Build the cargo manifest of the code, if the code needs dependencies
+    ///
+    /// The manifest is either written in a `//! ```cargo` header, or deduced from `use` and `extern crate` statements.
+    fn manifest(code: &str) -> Option<String> {
+        const PACKAGE: &str = "[package]\nname = \"aio-script\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n";
+        if let Some(header) = Self::manifest_header(code) {
+            if header.contains("[package]") {
+                return Some(header);
+            }
+            return Some(format!("{}{}", PACKAGE, header));
+        }
+        let crates = Self::external_crates(code);
+        if crates.is_empty() {
+            return None;
+        }
+        let mut manifest = format!("{}[dependencies]\n", PACKAGE);
+        for name in crates {
+            manifest.push_str(&Self::dependency(&name, &Self::package_name(&name)));
+        }
+        Some(manifest)
+    }
+    /// Line of the dependency in the manifest, with the default features of the crate
+    ///
+    /// The version `*` is the newest one already downloaded, since the project is built offline.
+    pub(crate) fn dependency(name: &str, package: &str) -> String {
+        let mut fields = vec!["version = \"*\"".to_string()];
+        if package != name {
+            fields.insert(0, format!("package = \"{}\"", package));
+        }
+        if let Some((_, features)) = DEFAULT_FEATURES.iter().find(|(n, _)| *n == name) {
+            fields.push(format!("features = {:?}", features));
+        }
+        match fields.len() {
+            1 => format!("{} = \"*\"\n", name),
+            _ => format!("{} = {{ {} }}\n", name, fields.join(", ")),
+        }
+    }
+    /// Package of the crate, `tokio_stream` is the package `tokio-stream`
+    ///
+    /// The name with `-` is only used if cargo downloaded this package and not the one with `_`.
+    fn package_name(name: &str) -> String {
+        let dashed = name.replace('_', "-");
+        if dashed != name && !Self::is_downloaded(name) && Self::is_downloaded(&dashed) {
+            dashed
+        } else {
+            name.to_string()
+        }
+    }
+    /// Whether a version of the package is in the cache of the cargo registries
+    fn is_downloaded(package: &str) -> bool {
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|| std::path::Path::new(filesystem::home_dir()).join(".cargo"));
+        let Ok(registries) = std::fs::read_dir(cargo_home.join("registry").join("cache")) else { return false };
+        let prefix = format!("{}-", package);
+        registries.flatten()
+            .filter_map(|registry| std::fs::read_dir(registry.path()).ok())
+            .flatten()
+            .flatten()
+            .any(|entry| entry.file_name().to_str()
+                .and_then(|file_name| file_name.strip_prefix(&prefix))
+                .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit())))
+    }
+    /// Extract the manifest from the leading `//!` comments
+    pub(crate) fn manifest_header(code: &str) -> Option<String> {
+        let mut lines = code.lines()
+            .map(str::trim_start)
+            .take_while(|l| l.starts_with("//!"))
+            .map(|l| l[3..].strip_prefix(' ').unwrap_or(&l[3..]));
+        lines.find(|l| matches!(l.trim(), "```cargo" | "cargo"))?;
+        let manifest = lines
+            .take_while(|l| l.trim() != "```")
+            .fold(String::new(), |acc, l| acc + l + "\n");
+        Some(manifest)
+    }
+    /// Crates of the `use` and `extern crate` statements, without the items declared by the code
+    pub(crate) fn external_crates(code: &str) -> Vec<String> {
+        static RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+            regex::Regex::new(r"(?m)^\s*(?:pub\s+)?(?:use\s+:{0,2}|extern\s+crate\s+)(?P<name>\w+)").expect("Failed to compile regex")
+        });
+        static DECLARATION: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+            regex::Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:async|const|unsafe)\s+)*(?:mod|enum|struct|union|trait|type|fn)\s+(?P<name>\w+)")
+                .expect("Failed to compile regex")
+        });
+        let declared = DECLARATION.captures_iter(code)
+            .map(|caps| caps["name"].to_string())
+            .collect::<Vec<_>>();
+        let mut crates = Vec::<String>::new();
+        for caps in RE.captures_iter(code) {
+            let name = &caps["name"];
+            if !BUILTIN_CRATES.contains(&name) && !declared.iter().any(|d| d == name) && !crates.iter().any(|c| c == name) {
+                crates.push(name.to_string());
+            }
+        }
+        crates
+    }
+    /// Name of the binary built from the manifest: the only `[[bin]]` target, or else the package
+    pub(crate) fn binary_name(manifest: &str) -> Option<String> {
+        let manifest = manifest.parse::<toml::Table>().ok()?;
+        let bin = manifest.get("bin")
+            .and_then(toml::Value::as_array)
+            .filter(|bins| bins.len() == 1)
+            .and_then(|bins| bins[0].get("name"));
+        bin.or_else(|| manifest.get("package")?.get("name"))?
+            .as_str()
+            .map(str::to_string)
+    }
+    /// Build the code in a cargo project and run it
+    ///
+    /// The project is cached by manifest, so the dependencies are built once.
+    fn run_cargo(cargo: &str, manifest: &str, code_block: &CodeBlock, options: &RunOptions) -> Result<std::process::Output, std::io::Error> {
+        let binary_name = Self::binary_name(manifest).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "the cargo manifest of the code block doesn't name a single binary")
+        })?;
+        let project_dir = std::path::Path::new(filesystem::cache_dir())
+            .join("rust")
+            .join(&crate::utils::sha256_hex(manifest)[..16]);
+        std::fs::create_dir_all(project_dir.join("src"))?;
+        let manifest_path = project_dir.join("Cargo.toml");
+        if std::fs::read_to_string(&manifest_path).ok().as_deref() != Some(manifest) {
+            std::fs::write(&manifest_path, manifest)?;
+        }
+        std::fs::write(project_dir.join("src").join("main.rs"), &code_block.code)?;
+
+        let compilation = std::process::Command::new(cargo)
+            .args(["build", "--offline", "--quiet", "--manifest-path"])
+            .arg(&manifest_path)
+            .spawn()?
+            .wait_with_output()?;
+        if !compilation.status.success() {
+            return Ok(compilation);
+        }
+
+        let binary_name = format!("{}{}", binary_name, std::env::consts::EXE_SUFFIX);
+        let mut process = std::process::Command::new(project_dir.join("target").join("debug").join(binary_name));
+        process.args(&options.args);
+        options.apply(&mut process)?;
+        process.spawn()?.wait_with_output()
+    }
+}
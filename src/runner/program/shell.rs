@@ -2,14 +2,26 @@ use super::*;
 pub struct ShellProgram(String);
     
 impl Program for ShellProgram {
-    fn run(&self, code_block: &CodeBlock) -> Result<std::process::Output, std::io::Error> {
+    fn run(&mut self, code_block: &CodeBlock, options: &RunOptions) -> Result<std::process::Output, std::io::Error> {
         let mut process = std::process::Command::new(&self.0);
         process
             .arg("-c")
             .arg(&code_block.code);
+        if !options.args.is_empty() {
+            if self.is_posix() {
+                // the first argument after the code is $0
+                process.arg(&self.0).args(&options.args);
+            } else {
+                log::warn!("Arguments are not supported by {}, they are ignored", self.0);
+            }
+        }
+        options.apply(&mut process)?;
         let child = process.spawn()?;
         child.wait_with_output()
     }
+    fn interpreter(&self) -> &str {
+        &self.0
+    }
 }
 impl ShellProgram {
     pub(super) fn search(list_shells: &[&'static str]) -> SearchStatus {
@@ -22,4 +34,8 @@ impl ShellProgram {
         }
         SearchStatus::NotFound
     }
-}
\ No newline at end of file
+    fn is_posix(&self) -> bool {
+        let name = std::path::Path::new(&self.0).file_stem().and_then(|s| s.to_str());
+        matches!(name, Some("sh" | "bash" | "zsh" | "dash" | "ksh"))
+    }
+}
@@ -0,0 +1,49 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SelectionError {
+    #[error("empty selection")]
+    Empty,
+    #[error("`{0}` is not a number")]
+    NotANumber(String),
+    #[error("index {0} out of range")]
+    OutOfRange(usize),
+    #[error("invalid range `{0}`")]
+    InvalidRange(String),
+}
+
+/// Parse a selection of code blocks
+///
+/// The selection is a comma separated list of indices (`1,4`) or ranges (`1-3`).
+/// `a` selects every code block. Indices start from 1 and are returned in the order of the selection.
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, SelectionError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(SelectionError::Empty);
+    }
+    if input == "a" {
+        return Ok((1..=count).collect());
+    }
+    let parse_index = |s: &str| -> Result<usize, SelectionError> {
+        let s = s.trim();
+        let index = s.parse::<usize>().map_err(|_| SelectionError::NotANumber(s.to_string()))?;
+        if !(1..=count).contains(&index) {
+            return Err(SelectionError::OutOfRange(index));
+        }
+        Ok(index)
+    };
+    let mut selection = Vec::new();
+    for item in input.split(',') {
+        match item.split_once('-') {
+            Some((begin, end)) => {
+                let (begin, end) = (parse_index(begin)?, parse_index(end)?);
+                if begin > end {
+                    return Err(SelectionError::InvalidRange(item.trim().to_string()));
+                }
+                selection.extend(begin..=end);
+            }
+            None => selection.push(parse_index(item)?),
+        }
+    }
+    Ok(selection)
+}
@@ -1,5 +1,7 @@
+#[cfg(feature = "local-llm")]
 use crate::generators::llama::stop::{stop_manager, StopManager};
 
+#[cfg(feature = "local-llm")]
 #[test]
 fn stops() {
     let mut manager = stop_manager!("Bonjour", "bonjour", "salut");
@@ -24,4 +26,593 @@ fn stops() {
         assert!(!manager.check(&c.to_string()));
     }
     assert!(manager.check(&'t'.to_string()));
-}
\ No newline at end of file
+}
+#[test]
+fn run_selection() {
+    use crate::runner::selection::{parse_selection, SelectionError};
+    assert_eq!(parse_selection("2", 4), Ok(vec![2]));
+    assert_eq!(parse_selection("1-3", 4), Ok(vec![1, 2, 3]));
+    assert_eq!(parse_selection("1,4", 4), Ok(vec![1, 4]));
+    assert_eq!(parse_selection(" 4, 1-2 ", 4), Ok(vec![4, 1, 2]));
+    assert_eq!(parse_selection("a", 3), Ok(vec![1, 2, 3]));
+    assert_eq!(parse_selection("", 3), Err(SelectionError::Empty));
+    assert_eq!(parse_selection("5", 4), Err(SelectionError::OutOfRange(5)));
+    assert_eq!(parse_selection("0", 4), Err(SelectionError::OutOfRange(0)));
+    assert_eq!(parse_selection("3-1", 4), Err(SelectionError::InvalidRange("3-1".into())));
+    assert_eq!(parse_selection("x", 4), Err(SelectionError::NotANumber("x".into())));
+}
+#[test]
+fn run_options() {
+    use crate::runner::program::{split_args, RunOptions, RunError};
+    assert_eq!(split_args("a 'b c' \"d\\\"e\"").unwrap(), ["a", "b c", "d\"e"]);
+    assert!(matches!(split_args("'a"), Err(RunError::InvalidArguments(_))));
+    let cwd = tempfile::tempdir().unwrap();
+    let mut stdin = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut stdin, b"input\n").unwrap();
+    let options = RunOptions {
+        args: split_args("a 'b c'").unwrap(),
+        stdin: Some(stdin.path().to_str().unwrap().to_string()),
+        cwd: Some(cwd.path().to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    let mut command = std::process::Command::new("sh");
+    command.args(["-c", "pwd; cat; printf '%s|' \"$@\"", "sh"]).args(&options.args);
+    options.apply(&mut command).unwrap();
+    let output = command.output().unwrap();
+    let expected = format!("{}\ninput\na|b c|", cwd.path().canonicalize().unwrap().display());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+#[test]
+fn run_chain() {
+    use crate::formatters::Formatter;
+    use crate::runner::{Runner, program::{chain, RunError}};
+    let mut runner = Runner::new(crate::args::RunChoice::No, true, Default::default(), Default::default());
+    runner.push("```sh\ncd /tmp\n```\n```bash\nls\n```\n```pwsh\nGet-Location\n```\n```python\nprint()\n```\n").unwrap();
+    let blocks = runner.code_blocks().iter().map(|block| (String::new(), block)).collect::<Vec<_>>();
+    let chained = chain(blocks[..2].iter().cloned()).unwrap();
+    assert_eq!((chained.language(), chained.code()), ("sh", "echo ''\ncd /tmp\necho ''\nls\n"));
+    assert!(matches!(chain(blocks[1..3].iter().cloned()), Err(RunError::MixedShells(language, _)) if language == "pwsh"));
+    assert!(matches!(chain(blocks[3..].iter().cloned()), Err(RunError::NotChainable(language)) if language == "python"));
+}
+#[test]
+fn run_python_venv() {
+    use crate::runner::program::python::{PythonProgram, ScriptMetadata};
+    let code = "# /// script\n# requires-python = \">=3.11\"\n# dependencies = [\n#   \"requests<3\",\n#   \"rich\",\n# ]\n# ///\nimport requests\n";
+    assert_eq!(ScriptMetadata::parse(code).unwrap().unwrap().dependencies, ["requests<3", "rich"]);
+    assert!(ScriptMetadata::parse("# /// script\n# ///\n").unwrap().unwrap().dependencies.is_empty());
+    assert!(ScriptMetadata::parse("import os\n# dependencies = [\"rich\"]\n").unwrap().is_none());
+    assert!(ScriptMetadata::parse("# /// script\n# dependencies = [\"rich\"]\n").is_err());
+    assert!(ScriptMetadata::parse("# /// script\n# dependencies = \"rich\n# ///\n").is_err());
+
+    let cache = tempfile::tempdir().unwrap();
+    let venv = PythonProgram::venv_dir(cache.path(), &["rich".into(), "requests<3".into()]);
+    assert_eq!(venv, PythonProgram::venv_dir(cache.path(), &["requests<3".into(), "rich".into()]));
+    assert_ne!(venv, PythonProgram::venv_dir(cache.path(), &["rich".into()]));
+    assert_eq!(venv.parent(), Some(cache.path().join("python").as_path()));
+    assert_eq!(venv.file_name().unwrap().len(), 16);
+    std::fs::create_dir_all(&venv).unwrap();
+    assert!(!PythonProgram::is_complete(&venv));
+    std::fs::write(venv.join(".aio-complete"), "requests<3\nrich").unwrap();
+    assert!(PythonProgram::is_complete(&venv));
+}
+#[test]
+fn run_cancel() {
+    use crate::formatters::{Formatter, json::JsonFormatter};
+    use crate::runner::Runner;
+    let document = "```sh\necho 1\n```\nText\n```sh\necho 2\n";
+    // the code block interrupted by the cancellation is dropped
+    let mut runner = Runner::new(crate::args::RunChoice::No, false, Default::default(), Default::default());
+    runner.push(document).unwrap();
+    assert_eq!(runner.code_blocks().len(), 2);
+    runner.cancel().unwrap();
+    let codes = runner.code_blocks().iter().map(|block| block.code()).collect::<Vec<_>>();
+    assert_eq!(codes, ["echo 1"]);
+    // a complete document is kept
+    let mut runner = Runner::new(crate::args::RunChoice::No, false, Default::default(), Default::default());
+    runner.push("```sh\necho 1\n```\n").unwrap();
+    runner.cancel().unwrap();
+    assert_eq!(runner.code_blocks().len(), 1);
+
+    let mut formatter = JsonFormatter::with_output(Vec::new());
+    formatter.push(document).unwrap();
+    formatter.cancel().unwrap();
+    let output = String::from_utf8(formatter.into_output()).unwrap();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert!(lines.contains(&r#"{"type":"finish","reason":"cancelled"}"#));
+    assert_eq!(lines.last(), Some(&r#"{"type":"token","token":"end_document"}"#));
+    let code_blocks = lines.iter().filter(|line| line.starts_with(r#"{"type":"code_block""#)).collect::<Vec<_>>();
+    assert_eq!(code_blocks, [&r#"{"type":"code_block","language":"sh","code":"echo 1"}"#]);
+    // the end of the stream keeps the unfinished code block
+    let mut formatter = JsonFormatter::with_output(Vec::new());
+    formatter.push(document).unwrap();
+    formatter.end_of_document().unwrap();
+    let output = String::from_utf8(formatter.into_output()).unwrap();
+    assert!(output.contains(r#"{"type":"code_block","language":"sh","code":"echo 2"}"#));
+}
+#[test]
+fn run_rust_manifest() {
+    use crate::runner::program::rust::RustProgram;
+    let code = "use std::io;\nuse serde::Deserialize;\nuse ::tokio_stream::StreamExt;\nextern crate rand;\n\
+        mod utils;\nuse utils::helper;\npub(crate) enum Color { Red }\nuse Color::*;\nuse self::utils::other;\nuse serde_json;\n\
+        fn main() {}\n";
+    assert_eq!(RustProgram::external_crates(code), ["serde", "tokio_stream", "rand", "serde_json"]);
+    assert!(RustProgram::external_crates("mod a { pub fn f() {} }\nuse a::f;\nfn main() { f() }").is_empty());
+    assert_eq!(RustProgram::dependency("rand", "rand"), "rand = \"*\"\n");
+    assert_eq!(RustProgram::dependency("serde", "serde"), "serde = { version = \"*\", features = [\"derive\"] }\n");
+    assert_eq!(RustProgram::dependency("tokio_stream", "tokio-stream"), "tokio_stream = { package = \"tokio-stream\", version = \"*\" }\n");
+
+    let code = "//! ```cargo\n//! [dependencies]\n//! rand = \"0.8\"\n//! ```\nuse rand::random;\n";
+    assert_eq!(RustProgram::manifest_header(code).as_deref(), Some("[dependencies]\nrand = \"0.8\"\n"));
+    assert_eq!(RustProgram::manifest_header("//! Documentation\nfn main() {}\n//! ```cargo\n"), None);
+    assert_eq!(RustProgram::binary_name("[package]\nname = \"demo\"\n[dependencies]\nrand = \"0.8\"\n").as_deref(), Some("demo"));
+    assert_eq!(RustProgram::binary_name("[package]\nname = \"demo\"\n[[bin]]\nname = \"tool\"\npath = \"src/main.rs\"\n").as_deref(), Some("tool"));
+    assert_eq!(RustProgram::binary_name("[dependencies]\nrand = \"0.8\"\n"), None);
+}
+#[test]
+fn run_history() {
+    use crate::runner::history::{append_to, load_from, RunRecord};
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("runs.jsonl");
+    assert!(load_from(&path).unwrap().is_empty());
+    let record = |code: &str, exit_code| RunRecord {
+        timestamp: chrono::Local::now(),
+        model: Some("gpt-4o".into()),
+        prompt: None,
+        input: "list the files".into(),
+        language: "sh".into(),
+        interpreter: "/bin/sh".into(),
+        code_hash: crate::utils::sha256_hex(code),
+        code: code.into(),
+        args: vec!["-a".into()],
+        stdin: None,
+        cwd: Some("/tmp".into()),
+        exit_code,
+        duration_ms: 12,
+        confirmation: crate::args::RunChoice::Force,
+    };
+    append_to(&path, &record("ls", Some(0))).unwrap();
+    std::fs::OpenOptions::new().append(true).open(&path).and_then(|mut file| std::io::Write::write_all(&mut file, b"{ malformed\n")).unwrap();
+    append_to(&path, &record("exit 2", None)).unwrap();
+    let records = load_from(&path).unwrap();
+    assert_eq!(records.len(), 2);
+    let first = &records[0];
+    assert_eq!((first.model.as_deref(), first.prompt.as_deref(), first.code.as_str(), first.exit_code), (Some("gpt-4o"), None, "ls", Some(0)));
+    assert_eq!((first.args.as_slice(), first.stdin.as_deref(), first.cwd.as_deref()), (["-a".to_string()].as_slice(), None, Some("/tmp")));
+    assert_eq!(first.code_hash, crate::utils::sha256_hex("ls"));
+    assert!(matches!(first.confirmation, crate::args::RunChoice::Force));
+    assert_eq!((records[1].code.as_str(), records[1].exit_code), ("exit 2", None));
+    assert!(append_to(&dir.path().join("missing").join("runs.jsonl"), &record("ls", Some(0))).is_err());
+}
+#[test]
+fn prompt_variables() {
+    use crate::config::{format_content, variables::{self, VariableError}};
+    let mut vars = variables::builtins();
+    vars.insert("input".into(), "list $files".into());
+    assert_eq!(format_content("$input on $os, $$input", &vars).unwrap(), format!("list $files on {}, $input", std::env::consts::OS));
+    assert!(matches!(format_content("$env_PATH", &vars), Err(VariableError::EnvNotAllowed(name)) if name == "PATH"));
+    variables::add_env(&mut vars, &variables::Config { env: vec!["PATH".into()] });
+    assert_eq!(format_content("$env_PATH", &vars).unwrap(), std::env::var("PATH").unwrap());
+    assert!(matches!(format_content("$input $lang", &vars), Err(VariableError::Missing(name)) if name == "lang"));
+    assert_eq!(variables::parse_definition("lang=rust=2021").unwrap(), ("lang".into(), "rust=2021".into()));
+    assert!(variables::parse_definition("=rust").is_err());
+}
+#[test]
+fn prompt_template() {
+    use crate::config::{template::{render, TemplateError}, variables::Variables};
+    let vars = Variables::from([("lang".to_string(), "rust".to_string()), ("files".to_string(), "a.rs\nb.rs\n".to_string())]);
+    let template = "{% if lang == \"rust\" %}\nRust\n{% elif lang %}Other{% else %}None{% endif %}\n\
+        {% for file in files %}\n- {{ file | upper }}\n{% endfor %}\n\
+        {{ style | default(\"short\") }} {{ not_set | default('') | trim }}";
+    assert_eq!(render(template, &vars).unwrap(), "Rust\n- A.RS\n- B.RS\nshort ");
+    assert!(matches!(render("a\n{{ missing }}", &vars), Err(TemplateError::Variable { line: 2, .. })));
+    assert!(matches!(render("{% if lang %}\n\nx", &vars), Err(TemplateError::Syntax { line: 1, .. })));
+    assert!(matches!(render("{{ lang | unknown }}", &vars), Err(TemplateError::Syntax { .. })));
+}
+#[test]
+fn prompt_template_include() {
+    use crate::config::{template::{render_in, TemplateError}, variables::Variables};
+    let root = tempfile::tempdir().unwrap();
+    let dir = root.path().join("aio");
+    std::fs::create_dir_all(dir.join("snippets")).unwrap();
+    std::fs::write(dir.join("snippets").join("style.md"), "Be {{ tone }}.").unwrap();
+    std::fs::write(root.path().join("secret"), "secret").unwrap();
+    let vars = Variables::from([("tone".to_string(), "brief".to_string())]);
+    assert_eq!(render_in("{% include \"snippets/style.md\" %}", &vars, &dir).unwrap(), "Be brief.");
+    assert_eq!(render_in("{% include \"snippets/../snippets/style.md\" %}", &vars, &dir).unwrap(), "Be brief.");
+    let secret = root.path().join("secret").display().to_string();
+    for path in ["../secret", "snippets/../../secret", secret.as_str()] {
+        let template = format!("{{% include \"{}\" %}}", path);
+        assert!(matches!(render_in(&template, &vars, &dir), Err(TemplateError::OutsideDir { .. })), "{}", path);
+    }
+}
+#[test]
+fn prompt_inheritance() {
+    use crate::config::prompt::{Fragments, Prompts, ResolveError};
+    let fragments = Fragments::from([("style".to_string(), "Be brief.".to_string())]);
+    let mut prompts: Prompts = serde_yaml::from_str(r#"
+- name: base
+  messages: [{ role: system, fragment: style }]
+  parameters: { temperature: 0.5, maxTokens: 100 }
+- name: child
+  extends: base
+  messages: [{ role: user, content: $input }]
+  parameters: { maxTokens: 200 }
+- name: other
+  extends: child
+  messages_mode: replace
+  messages: [{ role: system, content: Other }]
+"#).unwrap();
+    prompts.resolve(&fragments).unwrap();
+    let contents = |index: usize| prompts.0[index].messages.iter().map(|m| m.content.clone().unwrap()).collect::<Vec<_>>();
+    assert_eq!(contents(1), ["Be brief.", "$input"]);
+    assert_eq!(contents(2), ["Other"]);
+    assert_eq!((prompts.0[2].parameters.temperature, prompts.0[2].parameters.max_tokens), (Some(0.5), Some(200)));
+
+    let mut prompts: Prompts = serde_yaml::from_str("[{ name: a, extends: b }, { name: b, extends: a }]").unwrap();
+    assert!(matches!(prompts.resolve(&fragments), Err(ResolveError::Cycle(names)) if names == ["a", "b", "a"]));
+    let mut prompts: Prompts = serde_yaml::from_str("[{ name: a, messages: [{ role: user, fragment: none }] }]").unwrap();
+    assert!(matches!(prompts.resolve(&fragments), Err(ResolveError::UnknownFragment { index: 1, .. })));
+}
+#[test]
+fn prompt_library() {
+    use crate::config::library::parse_markdown;
+    let prompt = parse_markdown("---\nextends: ask\nparameters: { temperature: 0.2 }\n---\n## System\nBe brief.\n\n## Example\nText\n## user\n$input\n## assistant\n", "review").unwrap();
+    assert_eq!((prompt.name.as_str(), prompt.extends.as_deref(), prompt.parameters.temperature), ("review", Some("ask"), Some(0.2)));
+    let messages = prompt.messages.iter().map(|m| (m.role.lowercase(), m.content.as_deref())).collect::<Vec<_>>();
+    assert_eq!(messages, [("system", Some("Be brief.\n\n## Example\nText")), ("user", Some("$input")), ("assistant", None)]);
+    assert_eq!(parse_markdown("\ntext\n## user\n", "a").unwrap_err().0, 2);
+    assert!(parse_markdown("---\nname: a\n", "a").is_err());
+}
+#[test]
+fn config_layers() {
+    use crate::config::layers::{origin, Layers, Origin};
+    let [user, project] = [Origin::File("user.yml".into()), Origin::File(".aio.yml".into())];
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ markdown: { pager: false }, prompts: [{ name: ask, messages: [{ role: user }], parameters: { topP: 1 } }] }").unwrap(), user.clone());
+    layers.merge(serde_yaml::from_str("{ prompts: [{ name: ask, parameters: { temperature: 0.5 } }, { name: repo }] }").unwrap(), project.clone());
+    layers.merge_env([("AIO_PROMPTS__ASK__PARAMETERS__TOP_P".to_string(), "0.9".to_string()), ("AIO_MARKDOWN__PAGER".to_string(), "true".to_string())]).unwrap();
+    let expected: serde_yaml::Value = serde_yaml::from_str("{ markdown: { pager: true }, prompts: [{ name: ask, messages: [{ role: user }], parameters: { topP: 0.9, temperature: 0.5 } }, { name: repo }] }").unwrap();
+    assert_eq!(layers.value, expected);
+    assert_eq!(origin(&layers.origins, "prompts[ask].messages[0].role"), Some(&user));
+    assert_eq!(origin(&layers.origins, "prompts[ask].parameters.temperature"), Some(&project));
+    assert_eq!(origin(&layers.origins, "prompts[repo]"), Some(&project));
+    assert_eq!(origin(&layers.origins, "prompts[ask].parameters.topP"), Some(&Origin::Env("AIO_PROMPTS__ASK__PARAMETERS__TOP_P".into())));
+    assert!(layers.merge_env([("AIO_PROMPTS__NONE__NAME".to_string(), "x".to_string())]).is_err());
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ prompts: [{ name: ask }] }").unwrap(), user.clone());
+    layers.merge_env([("AIO_PROMPTS__ASK__PARAMETERS__MAX_TOKENS".to_string(), "10".to_string()), ("AIO_TOKEN".to_string(), "secret".to_string())]).unwrap();
+    let expected: serde_yaml::Value = serde_yaml::from_str("{ prompts: [{ name: ask, parameters: { maxTokens: 10 } }] }").unwrap();
+    assert_eq!(layers.value, expected);
+}
+#[test]
+fn config_project_layer() {
+    use crate::config::layers::Layers;
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ markdown: { pager_command: less } }").unwrap(), crate::config::layers::Origin::File("user.yml".into()));
+    let mut project = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut project, b"markdown: { pager: true, pager_command: 'sh -c evil' }\nrunner: { python: { uv: true } }\nprompts: [{ name: repo }]\n").unwrap();
+    let ignored = layers.merge_project_file(project.path()).unwrap();
+    assert_eq!(ignored, ["runner", "markdown.pager_command"]);
+    let expected: serde_yaml::Value = serde_yaml::from_str("{ markdown: { pager_command: less, pager: true }, prompts: [{ name: repo }] }").unwrap();
+    assert_eq!(layers.value, expected);
+}
+#[test]
+fn config_check() {
+    use crate::config::{check, layers::{Layers, Origin}};
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ prompts: [{ name: ask, parameters: { max-tokens: 3 } }], theme: { presett: dark } }").unwrap(), Origin::File("config.yml".into()));
+    let (config, warnings) = check::deserialize(&layers).unwrap();
+    assert_eq!(config.prompts.0[0].parameters.max_tokens, None);
+    let messages = warnings.iter().map(|w| w.message.as_str()).collect::<Vec<_>>();
+    assert_eq!(messages, [
+        "`prompts[ask].parameters.max-tokens`: unknown field `max-tokens`, did you mean `maxTokens`?",
+        "`theme.presett`: unknown field `presett`, did you mean `preset`?",
+    ]);
+    layers.merge_env([("AIO_STRICT".to_string(), "true".to_string())]).unwrap();
+    assert!(check::deserialize(&layers).is_err());
+    // the missing field is reported even after an unknown field is removed
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ theme: { presett: dark } }").unwrap(), Origin::File("config.yml".into()));
+    assert_eq!(check::deserialize(&layers).unwrap_err().message, "missing field `prompts`");
+    let mut layers = Layers::default();
+    layers.merge(serde_yaml::from_str("{ prompts: [{ name: ask, messages: [{ role: sytem }] }] }").unwrap(), Origin::Env("AIO_PROMPTS".into()));
+    let error = check::deserialize(&layers).unwrap_err();
+    assert_eq!(error.to_string(), "environment variable AIO_PROMPTS: `prompts[ask].messages[0].role`: unknown variant `sytem`, did you mean `system`?");
+}
+#[test]
+fn config_schema() {
+    use crate::config::schema::{config_schema, header};
+    let schema = serde_json::to_value(config_schema()).unwrap();
+    assert_eq!(schema["additionalProperties"], false);
+    let parameters = &schema["definitions"]["Parameters"];
+    assert_eq!(parameters["additionalProperties"], false);
+    assert!(parameters["properties"]["maxTokens"].is_object());
+    assert!(schema["properties"]["strict"]["description"].is_string());
+    assert_eq!(header("config.schema.json"), "# yaml-language-server: $schema=config.schema.json\n");
+}
+#[test]
+fn pager_rows() {
+    use crate::formatters::pager::LineFlattener;
+    let mut rows = LineFlattener::with_width(10);
+    // a long line without newline is wrapped by the terminal
+    rows.push("a".repeat(25).as_bytes());
+    assert_eq!(rows.rows(), 3);
+    rows.push(b"\n\x1b[1mab\x1b[0m\n\n");
+    assert_eq!(rows.rows(), 5);
+    // the cursor moves back to draw the line again
+    rows.push(b"0123456789\x1b[1G0123456789\n");
+    assert_eq!(rows.rows(), 6);
+    let mut lines = LineFlattener::default();
+    lines.push(b"not counted\n");
+    assert_eq!(lines.rows(), 0);
+}
+#[test]
+fn pager_lines() {
+    use crate::formatters::pager::LineFlattener;
+    let mut lines = LineFlattener::default();
+    // the escape sequence and the character are split between the writes
+    assert_eq!(lines.push(b"abcd\x1b["), b"");
+    assert_eq!(lines.push(b"2G\x1b[1mX\xc3"), b"");
+    assert_eq!(lines.push(b"\xa9\x1b[K\n"), "a\x1b[1mX\u{e9}\n".as_bytes());
+    assert_eq!(lines.push(b"\x1b]8;;http://a.b\x1b\\link\x1b]8;;\x1b\\"), b"");
+    assert_eq!(lines.finish(), b"\x1b]8;;http://a.b\x1b\\link\x1b]8;;\x1b\\");
+}
+mod markdown {
+    use crate::formatters::Formatter;
+    use crate::formatters::markdown::{Parser, RecordingRenderer, TerminalRenderer, HtmlRenderer, PlainRenderer, token::{Token, Marker, InlineStyleToken}};
+
+    fn parse_chunks<'a>(chunks: impl IntoIterator<Item = &'a str>) -> Vec<Token> {
+        let mut parser = Parser::new(RecordingRenderer::default());
+        for chunk in chunks {
+            parser.push(chunk).unwrap();
+        }
+        parser.end_of_document().unwrap();
+        let mut tokens = parser.into_renderer().into_merged_tokens();
+        assert_eq!(tokens.pop(), Some(Token::EndDocument));
+        tokens
+    }
+    fn parse(input: &str) -> Vec<Token> {
+        parse_chunks([input])
+    }
+    /// Split the input in chunks of 1 to 8 characters, with a xorshift generator
+    fn random_chunks(input: &str, seed: u64) -> Vec<&str> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        let mut chunks = Vec::new();
+        let mut rest = input;
+        while !rest.is_empty() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let length = (state % 8) as usize + 1;
+            let end = rest.char_indices().nth(length).map(|(i, _)| i).unwrap_or(rest.len());
+            let (chunk, remaining) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = remaining;
+        }
+        chunks
+    }
+
+    /// The tokens of the documents of `tests/golden` are compared to the `.tokens` files,
+    /// whatever the chunks the document is split into.
+    ///
+    /// Run with `UPDATE_GOLDEN=1` to write the `.tokens` files again.
+    #[test]
+    fn golden() {
+        let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden");
+        let mut documents = std::fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "md"))
+            .collect::<Vec<_>>();
+        documents.sort();
+        assert!(!documents.is_empty());
+        for path in documents {
+            let input = std::fs::read_to_string(&path).unwrap();
+            let tokens = parse(&input);
+            let rendered = tokens.iter().map(|token| format!("{:?}\n", token)).collect::<String>();
+            let golden_path = path.with_extension("tokens");
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::write(&golden_path, &rendered).unwrap();
+            } else {
+                let expected = std::fs::read_to_string(&golden_path).unwrap();
+                assert_eq!(rendered, expected, "{}", path.display());
+            }
+            let chars = input.chars().map(|c| c.to_string()).collect::<Vec<_>>();
+            assert_eq!(parse_chunks(chars.iter().map(String::as_str)), tokens, "{} by character", path.display());
+            for seed in 0..64 {
+                assert_eq!(parse_chunks(random_chunks(&input, seed)), tokens, "{} with seed {}", path.display(), seed);
+            }
+        }
+    }
+    #[test]
+    fn code_language() {
+        // the first character of the language is parsed with the backticks
+        for chunks in [vec!["```rust\nlet a = 1;\n```\n"], vec!["``", "`r", "ust\nlet a = 1;\n```\n"], vec!["```", "r", "ust\n", "let a = 1;\n```\n"]] {
+            let tokens = parse_chunks(chunks.iter().copied());
+            assert_eq!(tokens[..3], [Token::BeginCode, Token::Text("rust".into()), Token::Newline], "{:?}", chunks);
+        }
+        assert_eq!(parse("```c\nint a;\n```\n")[..3], [Token::BeginCode, Token::Text("c".into()), Token::Newline]);
+        use crate::formatters::markdown::renderer::highlight::Highlighter;
+        assert!(Highlighter::new("ust", "base16-ocean.dark").is_none());
+        let mut highlighter = Highlighter::new("rust", "base16-ocean.dark").unwrap();
+        let ranges = highlighter.highlight("let a = 1;\n").unwrap();
+        assert!(ranges.len() > 1);
+        assert_eq!(ranges.iter().map(|(_, text)| *text).collect::<String>(), "let a = 1;\n");
+    }
+    #[test]
+    fn terminal_output() {
+        let config = crate::formatters::markdown::config::Config::default();
+        // the width is given, the size of the terminal running the tests doesn't matter
+        let mut parser = Parser::new(TerminalRenderer::with_output(Vec::new(), &config, 40));
+        parser.push("# Title\nSome **bold** text\n```sh\necho hello\n```\n").unwrap();
+        parser.push(&"word ".repeat(20)).unwrap();
+        parser.end_of_document().unwrap();
+        let output = String::from_utf8(parser.into_renderer().into_output()).unwrap();
+        assert!(output.contains("Title"));
+        assert!(output.contains("bold"));
+        assert!(output.contains("echo hello"));
+        let rule = crate::formatters::markdown::theme::current().chars.lines[0].to_string();
+        assert!(output.contains(&rule.repeat(40)) && !output.contains(&rule.repeat(41)));
+        // 20 words of 5 columns on lines of 40 columns
+        assert_eq!(output.lines().filter(|line| line.contains("word")).count(), 3);
+    }
+    /// Text written by the terminal renderer, with the moves of the cursor applied and without the styles
+    fn terminal_text(input: &str, width: usize, hyperlinks: bool) -> String {
+        let config = crate::formatters::markdown::config::Config::default();
+        let mut parser = Parser::new(TerminalRenderer::with_output(Vec::new(), &config, width).with_hyperlinks(hyperlinks));
+        parser.push(input).unwrap();
+        parser.end_of_document().unwrap();
+        let output = parser.into_renderer().into_output();
+        let mut lines = crate::formatters::pager::LineFlattener::default();
+        let mut flattened = lines.push(&output);
+        flattened.extend(lines.finish());
+        regex::Regex::new("\x1b\\[[0-9;]*m").unwrap().replace_all(std::str::from_utf8(&flattened).unwrap(), "").into_owned()
+    }
+    #[test]
+    fn terminal_wrapping() {
+        let text = terminal_text("- first item with some long words\n  1. nested item wrapping\n\n> quoted text wrapping under the bar\n", 20, false);
+        let chars = crate::formatters::markdown::theme::current().chars;
+        let (bullet, bar) = (chars.bullets[0], chars.lines[1]);
+        // the wrapped lines begin under the text of the item and with the bar of the quote
+        assert_eq!(text, format!("{bullet} first item with \n  some long words\n  1. nested item \n     wrapping\n\n\
+            {bar} quoted text \n{bar} wrapping under the\n{bar} bar\n\n"));
+    }
+    #[test]
+    fn terminal_links() {
+        let input = "> quoted **text**\n> second line\n\nSee [the docs](https://a.b/docs) and ![logo](logo.png).\n";
+        let bar = crate::formatters::markdown::theme::current().chars.lines[1];
+        let quote = format!("{bar} quoted text\n{bar} second line\n\n");
+        // without hyperlinks, the url follows the text of the link
+        assert_eq!(terminal_text(input, 80, false), format!("{quote}See the docs (https://a.b/docs) and [image: logo](logo.png).\n\n"));
+        // the OSC 8 sequences surround the text of the link
+        assert_eq!(terminal_text(input, 80, true), format!("{quote}See \x1b]8;;https://a.b/docs\x1b\\the docs\x1b]8;;\x1b\\ and [image: logo](logo.png).\n\n"));
+        let table = "| [a](u) |\n|---|\n| x |\n";
+        assert_eq!(terminal_text(table, 80, false), "┌───────┐\n│ a (u) │\n├───────┤\n│ x     │\n└───────┘\n\n");
+        assert_eq!(terminal_text(table, 80, true), "┌───┐\n│ \x1b]8;;u\x1b\\a\x1b]8;;\x1b\\ │\n├───┤\n│ x │\n└───┘\n\n");
+    }
+    #[test]
+    fn terminal_table() {
+        let input = "| Left | Center | Right |\n|:--|:-:|--:|\n| a | 漢字 | 1 |\n| long cell to wrap | b | 22 |\n";
+        // the columns fit in the width, the wide characters take two columns
+        assert_eq!(terminal_text(input, 80, false), "\
+            ┌───────────────────┬────────┬───────┐\n\
+            │ Left              │ Center │ Right │\n\
+            ├───────────────────┼────────┼───────┤\n\
+            │ a                 │  漢字  │     1 │\n\
+            │ long cell to wrap │   b    │    22 │\n\
+            └───────────────────┴────────┴───────┘\n\n");
+        // the widest column is shrunk, and its cells are wrapped on the spaces
+        assert_eq!(terminal_text(input, 30, false), "\
+            ┌───────────┬────────┬───────┐\n\
+            │ Left      │ Center │ Right │\n\
+            ├───────────┼────────┼───────┤\n\
+            │ a         │  漢字  │     1 │\n\
+            │ long cell │   b    │    22 │\n\
+            │ to wrap   │        │       │\n\
+            └───────────┴────────┴───────┘\n\n");
+        // a word longer than the column is cut
+        assert_eq!(terminal_text("| a |\n|---|\n| abcdef gh |\n", 7, false), "┌─────┐\n│ a   │\n├─────┤\n│ abc │\n│ def │\n│ gh  │\n└─────┘\n\n");
+    }
+    #[test]
+    fn html_output() {
+        let config = crate::formatters::markdown::config::Config::default();
+        let mut parser = Parser::new(HtmlRenderer::with_output(Vec::new(), &config));
+        parser.push("# Title\nSome **bold** <text>\n\n- one\n  - two\n\n```rust\nlet x = 1;\n```\n").unwrap();
+        parser.end_of_document().unwrap();
+        let output = String::from_utf8(parser.into_renderer().into_output()).unwrap();
+        assert!(output.starts_with("<!DOCTYPE html>"));
+        assert!(output.contains("<h1>Title</h1>\n<p>Some <strong>bold</strong> &lt;text&gt;</p>\n"));
+        assert!(output.contains("<ul>\n<li>one<ul>\n<li>two</li>\n</ul>\n</li>\n</ul>\n"));
+        assert!(output.contains("<code class=\"language-rust\"><span class=\"syntax-"));
+        assert!(output.ends_with("</code></pre>\n</body>\n</html>\n"));
+
+        let mut parser = Parser::new(HtmlRenderer::with_output(Vec::new(), &config));
+        parser.push("[site](https://a.b) [doc](docs/a.md) [x](javascript:alert(1)) ![](JavaScript:alert(2)) [y]( data:text/html,x)\n").unwrap();
+        parser.end_of_document().unwrap();
+        let output = String::from_utf8(parser.into_renderer().into_output()).unwrap();
+        assert!(output.contains("<a href=\"https://a.b\">site</a> <a href=\"docs/a.md\">doc</a> x (javascript:alert(1))  (JavaScript:alert(2)) y (data:text/html,x)"));
+    }
+    #[test]
+    fn plain_output() {
+        let mut parser = Parser::new(PlainRenderer::with_output(Vec::new()));
+        parser.push("# Title\nSome **bold** [link](http://a.b)\n- [x] done\n\n| a | b |\n|---|--:|\n| ab | 1 |\n```sh\necho hi\n```\n").unwrap();
+        parser.end_of_document().unwrap();
+        let output = String::from_utf8(parser.into_renderer().into_output()).unwrap();
+        assert_eq!(output, "Title\nSome bold link (http://a.b)\n- [x] done\n\na   b\n--  -\nab  1\n    echo hi\n");
+    }
+    #[test]
+    fn json_output() {
+        use crate::formatters::json::JsonFormatter;
+        use crate::generators::{Event, Usage};
+        let mut formatter = JsonFormatter::with_output(Vec::new());
+        formatter.push("Hi\n```sh\necho 1\n```\n").unwrap();
+        formatter.push_event(&Event::Finish("stop".into())).unwrap();
+        formatter.push_event(&Event::Usage(Usage { prompt_tokens: 1, completion_tokens: 2, total_tokens: 3 })).unwrap();
+        formatter.end_of_document().unwrap();
+        let output = String::from_utf8(formatter.into_output()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], r#"{"type":"text","text":"Hi\n```sh\necho 1\n```\n"}"#);
+        assert_eq!(lines[1], r#"{"type":"token","token":{"text":"Hi"}}"#);
+        assert!(lines.contains(&r#"{"type":"code_block","language":"sh","code":"echo 1"}"#));
+        assert!(lines.contains(&r#"{"type":"finish","reason":"stop"}"#));
+        assert!(lines.contains(&r#"{"type":"usage","prompt_tokens":1,"completion_tokens":2,"total_tokens":3}"#));
+        assert_eq!(lines.last(), Some(&r#"{"type":"token","token":"end_document"}"#));
+    }
+    #[test]
+    fn theme_styles() {
+        use crate::formatters::markdown::theme::Style;
+        let style = Style::try_from("italic  #ff8800 on_dark_grey bold").unwrap();
+        assert_eq!(String::from(style), "bold italic #ff8800 on_dark_grey");
+        assert_eq!(String::from(Style::try_from("196").unwrap()), "196");
+        assert_eq!(Style::try_from("").unwrap(), Style::default());
+        assert!(Style::try_from("blinking").is_err());
+    }
+    fn text(s: &str) -> Token {
+        Token::Text(s.to_string())
+    }
+    fn begin(style: InlineStyleToken) -> Token {
+        Token::InlineStyle(Marker::Begin(style))
+    }
+    fn end(style: InlineStyleToken) -> Token {
+        Token::InlineStyle(Marker::End(style))
+    }
+
+    #[test]
+    fn inline_styles() {
+        use InlineStyleToken::*;
+        assert_eq!(parse("a **b** c"), vec![text("a "), begin(TwoStars), text("b"), end(TwoStars), text(" c")]);
+        assert_eq!(parse("~~old~~ new"), vec![begin(TwoTildes), text("old"), end(TwoTildes), text(" new")]);
+        assert_eq!(parse("snake_case_name"), vec![text("snake_case_name")]);
+        assert_eq!(parse("a ~ b ~~ c"), vec![text("a ~ b ~~ c")]);
+        assert_eq!(parse("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+    }
+    #[test]
+    fn code_spans() {
+        use InlineStyleToken::*;
+        assert_eq!(parse("`a*b*`"), vec![begin(OneQuote), text("a*b*"), end(OneQuote)]);
+        assert_eq!(parse("`` a`b ``"), vec![begin(TwoQuotes), text(" a`b "), end(TwoQuotes)]);
+        assert_eq!(parse("`[x](y) $z$`"), vec![begin(OneQuote), text("[x](y) $z$"), end(OneQuote)]);
+        assert_eq!(parse("`\\*`"), vec![begin(OneQuote), text("\\*"), end(OneQuote)]);
+    }
+    #[test]
+    fn escapes() {
+        assert_eq!(parse("\\*not italic\\*"), vec![text("*not italic*")]);
+        assert_eq!(parse("\\# title"), vec![text("# title")]);
+        assert_eq!(parse("a\\`b \\\\ c\\d"), vec![text("a`b \\ c\\d")]);
+        assert_eq!(parse("\\$5"), vec![text("$5")]);
+    }
+    #[test]
+    fn inline_math() {
+        use InlineStyleToken::*;
+        let math = |s: &str| vec![begin(OneDollar), text(s), end(OneDollar)];
+        assert_eq!(parse("$x^2 + y_1$"), math("x² + y₁"));
+        assert_eq!(parse("$\\alpha \\leq \\Omega$"), math("α ≤ Ω"));
+        assert_eq!(parse("$\\frac{1}{2}$"), math("¹⁄₂"));
+        assert_eq!(parse("$\\frac{a+b}{c}$"), math("(a+b)/c"));
+        assert_eq!(parse("$e^{i\\pi}$"), math("e^(iπ)"));
+        assert_eq!(parse("$\\sqrt{x+1}$"), math("√(x+1)"));
+        assert_eq!(parse("$\\unknown$"), math("\\unknown"));
+        assert_eq!(parse("costs $5 and $10"), vec![text("costs $5 and $10")]);
+        assert_eq!(parse("$$"), vec![text("$$")]);
+        assert_eq!(parse("$ x$"), vec![text("$ x$")]);
+    }
+}
@@ -1,18 +1,7 @@
 use tokio_stream::Stream;
-use std::pin::Pin;
 
 pub trait FlattenTrait: Stream
 {
-    fn flatten_iter(self) -> Flatten<StreamIter<Self, <Self as Stream>::Item>>
-    where 
-        Self: Sized,
-        Self::Item: Iterator
-    {
-        Flatten {
-            stream: StreamIter(self),
-            current: None
-        }
-    }
     fn flatten_result_iter<It, E>(self) -> Flatten<StreamResultIter<Self, It, E>>
     where
         Self: Sized + Stream<Item=Result<It, E>>,
@@ -34,24 +23,6 @@ where
     stream: St,
     current: Option<<St as Stream>::Item>
 }
-#[pin_project::pin_project]
-pub struct StreamIter<St, It>(#[pin] St)
-where
-    St: Stream<Item=It>,
-    It: Iterator;
-
-impl<St, It> Stream for StreamIter<St, It>
-where
-    St: Stream<Item=It>,
-    It: Iterator
-{
-    type Item = St::Item;
-    #[inline]
-    fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<<Self as Stream>::Item>> {
-        self.as_mut().project().0.poll_next(cx)
-    }
-}
-
 #[pin_project::pin_project]
 pub struct StreamResultIter<St, It, E>(#[pin] St)
 where
@@ -112,41 +83,4 @@ where
             }
         }
     }
-}
-
-// Implementation of Flatten for Iterator
-
-impl<St, It> Stream for Flatten<StreamIter<St, It>>
-where
-    St: Stream<Item = It>,
-    It: Iterator
-{
-    type Item = <It as Iterator>::Item;
-    fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<<Self as Stream>::Item>> {
-        use std::task::Poll::*;
-        let self_project = self.as_mut().project();
-        match self_project.current {
-            current @ Some(_) => {
-                let item = unsafe { current.as_mut().unwrap_unchecked().next()};
-                match item {
-                    None => {
-                        *current = None;
-                        self.poll_next(cx)
-                    }
-                    v => Ready(v),
-                }
-            }
-            current @ None => {
-                let item = self_project.stream.poll_next(cx);
-                match item {
-                    Ready(Some(item)) => {
-                        *current = Some(item);
-                        self.poll_next(cx)
-                    }
-                    Ready(None) => Ready(None),
-                    Pending => Pending,
-                }
-            }
-        }
-    }
 }
\ No newline at end of file
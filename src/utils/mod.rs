@@ -1,24 +1,15 @@
 pub mod split_bytes;
 pub mod flatten_stream;
 
-pub use split_bytes::{SplitBytes, SplitBytesFactory};
+pub use split_bytes::SplitBytesFactory;
 pub use flatten_stream::FlattenTrait;
 
-macro_rules! hashmap {
-    ($($name:ident => $value:expr),*) => {{
-        let mut map = ::std::collections::HashMap::new();
-        $(map.insert({stringify!($name)}.into(), {$value}.into());)*
-        map
-    }};
-}
-
-pub(crate) use hashmap;
-
-pub fn append_to_vec<T: Copy>(vec: &mut Vec<T>, other: &[T]) {
-    vec.reserve(other.len());
-    other.iter().for_each(|v| vec.push(*v));
+pub fn sha256_hex<T: AsRef<[u8]>>(data: T) -> String {
+    use sha2::Digest;
+    format!("{:x}", sha2::Sha256::digest(data.as_ref()))
 }
 
+#[cfg(feature = "local-llm")]
 macro_rules! vec_merge {
     ($tokens:ident, $($other_tokens:expr),*) => {{
         let arrs = [$($other_tokens),*];
@@ -27,4 +18,5 @@ macro_rules! vec_merge {
     }};
 }
 
+#[cfg(feature = "local-llm")]
 pub(crate) use vec_merge;
\ No newline at end of file
@@ -0,0 +1,29 @@
+//! The output of the json formatter is only made of json lines, the logs are written to stderr
+
+use std::process::Command;
+
+#[test]
+fn json_output_with_logs() {
+    let home = tempfile::tempdir().unwrap();
+    let config_dir = home.path().join(".config").join("aio");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::create_dir_all(home.path().join(".cache")).unwrap();
+    std::fs::write(config_dir.join("config.yml"), "prompts: []\nlocal:\n  models: []\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aio"))
+        .args(["--formatter", "json", "--run", "ask", "-vv", "from-content", "Hi\n```sh\necho hi\n```\n"])
+        .env("HOME", home.path())
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_CACHE_HOME")
+        .current_dir(home.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.is_empty());
+    for line in stdout.lines() {
+        assert!(serde_json::from_str::<serde_json::Value>(line).is_ok(), "not a json line: {}", line);
+    }
+    // the code blocks are not run without `--run force`, and a warning is logged
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--run force"));
+}